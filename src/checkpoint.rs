@@ -0,0 +1,152 @@
+use crate::parser::GNetTrackRecord;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Latest uploaded timestamp per input file, persisted as JSON so re-runs can skip records
+/// that already reached InfluxDB.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    entries: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl Checkpoint {
+    /// Reads the checkpoint at `path`. A missing or unreadable file starts an empty checkpoint.
+    pub fn load(path: &Path) -> Self {
+        let entries = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!(
+                    "Ignoring corrupt checkpoint {}: {e}; starting fresh",
+                    path.display()
+                );
+                BTreeMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("No checkpoint at {}; starting fresh", path.display());
+                BTreeMap::new()
+            }
+            Err(e) => {
+                warn!(
+                    "Cannot read checkpoint {}: {e}; starting fresh",
+                    path.display()
+                );
+                BTreeMap::new()
+            }
+        };
+
+        Self {
+            path: path.to_path_buf(),
+            entries,
+        }
+    }
+
+    /// Timestamp of the newest record uploaded from `input`, if any.
+    pub fn last_uploaded(&self, input: &str) -> Option<DateTime<Utc>> {
+        self.entries.get(&Self::key(input)).copied()
+    }
+
+    /// Advances the entry for `input` past the first `uploaded` of its `records`.
+    pub fn record(&mut self, input: &str, records: &[GNetTrackRecord], uploaded: usize) {
+        if let Some(timestamp) = Self::uploaded_through(records, uploaded) {
+            self.advance(input, timestamp);
+        }
+    }
+
+    /// Newest timestamp up to which every record has been uploaded when only the first
+    /// `uploaded` of `records` were. Records after them may be older than some before them.
+    pub fn uploaded_through(records: &[GNetTrackRecord], uploaded: usize) -> Option<DateTime<Utc>> {
+        let (done, pending) = records.split_at(uploaded.min(records.len()));
+        let oldest_pending = pending.iter().map(|r| r.timestamp).min();
+        done.iter()
+            .map(|r| r.timestamp)
+            .filter(|timestamp| oldest_pending.is_none_or(|oldest| *timestamp < oldest))
+            .max()
+    }
+
+    /// Advances the entry for `input` to `timestamp` unless it is already later.
+    pub fn advance(&mut self, input: &str, timestamp: DateTime<Utc>) {
+        let entry = self.entries.entry(Self::key(input)).or_insert(timestamp);
+        *entry = (*entry).max(timestamp);
+    }
+
+    /// Writes the checkpoint, replacing the previous file atomically.
+    pub fn save(&self) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&self.entries)?)
+            .with_context(|| format!("Failed to write checkpoint {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to write checkpoint {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Keys inputs by their absolute path so runs from different directories agree.
+    fn key(input: &str) -> String {
+        fs::canonicalize(input)
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| input.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::record;
+
+    fn at(second: u32) -> GNetTrackRecord {
+        record(&format!(
+            r#"{{"timestamp": "2025-10-03T10:20:{second:02}Z"}}"#
+        ))
+    }
+
+    #[test]
+    fn resumes_from_a_saved_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+
+        let mut checkpoint = Checkpoint::load(&path);
+        assert_eq!(checkpoint.last_uploaded("a.csv"), None);
+        checkpoint.record("a.csv", &[at(1), at(2), at(3)], 2);
+        checkpoint.record("b.csv", &[at(5)], 1);
+        checkpoint.save().unwrap();
+
+        let resumed = Checkpoint::load(&path);
+        assert_eq!(resumed.last_uploaded("a.csv"), Some(at(2).timestamp));
+        assert_eq!(resumed.last_uploaded("b.csv"), Some(at(5).timestamp));
+    }
+
+    #[test]
+    fn stops_before_the_oldest_pending_record() {
+        let records = [at(3), at(1), at(4), at(2)];
+        // at(2) is still pending, so only at(1) is safely behind the checkpoint
+        assert_eq!(
+            Checkpoint::uploaded_through(&records, 3),
+            Some(at(1).timestamp)
+        );
+        assert_eq!(
+            Checkpoint::uploaded_through(&records, 4),
+            Some(at(4).timestamp)
+        );
+        assert_eq!(Checkpoint::uploaded_through(&records, 0), None);
+    }
+
+    #[test]
+    fn never_moves_backwards() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut checkpoint = Checkpoint::load(&dir.path().join("checkpoint.json"));
+        checkpoint.advance("a.csv", at(5).timestamp);
+        checkpoint.advance("a.csv", at(3).timestamp);
+        assert_eq!(checkpoint.last_uploaded("a.csv"), Some(at(5).timestamp));
+    }
+
+    #[test]
+    fn starts_fresh_from_a_corrupt_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+        fs::write(&path, "{not json").unwrap();
+        assert_eq!(Checkpoint::load(&path).last_uploaded("a.csv"), None);
+    }
+}
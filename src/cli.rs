@@ -0,0 +1,405 @@
+use anyhow::{Result, anyhow};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+/// What a run does, chosen by the subcommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Parse inputs and upload them (also the behavior without a subcommand)
+    Upload,
+    /// Parse inputs and write them to a file
+    Convert,
+    /// Test the InfluxDB connection
+    Test,
+    /// Parse inputs and print summary statistics
+    Stats,
+}
+
+pub fn build() -> Command {
+    // Without a subcommand the original flat flags are still accepted, so they are defined
+    // both on the top-level command and on the subcommands that use them.
+    Command::new("gnt2influx")
+        .version("0.1.0")
+        .author("Your Name")
+        .about("Converts G-NetTrack Lite log files to InfluxDB format and uploads them")
+        .subcommand_negates_reqs(true)
+        .args(global_args())
+        .args(input_args(false))
+        .args(constant_args())
+        .args(output_args(false))
+        .args(upload_args())
+        .args(legacy_args())
+        .subcommand(
+            Command::new("upload")
+                .about("Parse log files and upload them to InfluxDB")
+                .args(input_args(true))
+                .args(constant_args())
+                .args(upload_args())
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("With --dry-run, write the line protocol that would be uploaded to FILE")
+                        .requires("dry-run"),
+                ),
+        )
+        .subcommand(
+            Command::new("convert")
+                .about("Parse log files and write the records to a file")
+                .args(input_args(true))
+                .args(constant_args())
+                .args(output_args(true)),
+        )
+        .subcommand(Command::new("test").about("Test the InfluxDB connection"))
+        .subcommand(
+            Command::new("stats")
+                .about("Parse log files and print summary statistics")
+                .args(input_args(true)),
+        )
+}
+
+/// Arguments accepted before and after a subcommand.
+const GLOBAL_ARGS: &[&str] = &[
+    "config",
+    "config-dir",
+    "profile",
+    "verbose",
+    "quiet",
+    "log-format",
+];
+
+/// Resolves the mode and the matches holding its arguments. Global arguments are available
+/// from either.
+pub fn mode(matches: &ArgMatches) -> Result<(Mode, &ArgMatches)> {
+    let Some((name, sub)) = matches.subcommand() else {
+        if flag(matches, "test-connection") {
+            return Ok((Mode::Test, matches));
+        }
+        return Ok((Mode::Upload, matches));
+    };
+
+    // The flat flags also exist on the top-level command, where they would be silently
+    // ignored once a subcommand is given
+    if let Some(id) = matches.ids().find(|id| {
+        !GLOBAL_ARGS.contains(&id.as_str())
+            && matches.value_source(id.as_str()) == Some(ValueSource::CommandLine)
+    }) {
+        return Err(anyhow!(
+            "--{id} must be given after the `{name}` subcommand"
+        ));
+    }
+
+    let mode = match name {
+        "upload" => Mode::Upload,
+        "convert" => Mode::Convert,
+        "test" => Mode::Test,
+        "stats" => Mode::Stats,
+        other => return Err(anyhow!("Unknown subcommand: {other}")),
+    };
+    Ok((mode, sub))
+}
+
+/// Reads a flag, treating flags the subcommand doesn't define as unset.
+pub fn flag(matches: &ArgMatches, id: &str) -> bool {
+    matches
+        .try_get_one::<bool>(id)
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+/// Reads a value, treating arguments the subcommand doesn't define as absent.
+pub fn value<'a, T>(matches: &'a ArgMatches, id: &str) -> Option<&'a T>
+where
+    T: std::any::Any + Clone + Send + Sync + 'static,
+{
+    matches.try_get_one::<T>(id).ok().flatten()
+}
+
+/// Reads every value of a repeatable argument.
+pub fn values<'a>(matches: &'a ArgMatches, id: &str) -> impl Iterator<Item = &'a String> {
+    matches
+        .try_get_many::<String>(id)
+        .ok()
+        .flatten()
+        .into_iter()
+        .flatten()
+}
+
+fn global_args() -> Vec<Arg> {
+    vec![
+        Arg::new("config")
+            .short('c')
+            .long("config")
+            .value_name("FILE")
+            .help("Path to configuration file")
+            .default_value("config.toml")
+            .global(true),
+        Arg::new("config-dir")
+            .long("config-dir")
+            .value_name("DIR")
+            .help("Directory containing config.toml and config.<profile>.toml files")
+            .default_value(".")
+            .global(true),
+        Arg::new("profile")
+            .long("profile")
+            .value_name("NAME")
+            .help("Apply the [profiles.<NAME>] table and config.<NAME>.toml from the config directory over the base config")
+            .global(true),
+        Arg::new("verbose")
+            .short('v')
+            .long("verbose")
+            .help("Enable verbose logging")
+            .action(ArgAction::SetTrue)
+            .global(true),
+        Arg::new("quiet")
+            .short('q')
+            .long("quiet")
+            .help("Only log errors")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("verbose")
+            .global(true),
+        Arg::new("log-format")
+            .long("log-format")
+            .value_name("FORMAT")
+            .help("Log line format")
+            .value_parser(["text", "json"])
+            .default_value("text")
+            .global(true),
+    ]
+}
+
+/// Flags only accepted without a subcommand.
+fn legacy_args() -> Vec<Arg> {
+    vec![
+        Arg::new("init-config")
+            .long("init-config")
+            .value_name("FILE")
+            .help("Write a default configuration file and exit")
+            .num_args(0..=1)
+            .default_missing_value("config.toml"),
+        Arg::new("force")
+            .long("force")
+            .help("Overwrite an existing file with --init-config")
+            .action(ArgAction::SetTrue),
+        Arg::new("test-connection")
+            .long("test-connection")
+            .help("Test InfluxDB connection without uploading data (deprecated, use `test`)")
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+/// Input selection and record processing, shared by every subcommand that parses logs.
+fn input_args(required: bool) -> Vec<Arg> {
+    let input = Arg::new("input")
+        .short('i')
+        .long("input")
+        .value_name("FILE")
+        .help("Path or http(s) URL of a G-NetTrack log file, or - for stdin (can be given multiple times)")
+        .num_args(1..)
+        .action(ArgAction::Append);
+    let input = if required {
+        input.required(true)
+    } else {
+        input.required_unless_present_any(["test-connection", "init-config"])
+    };
+
+    vec![
+        input,
+        Arg::new("format")
+            .long("format")
+            .value_name("FORMAT")
+            .help("Input format, overriding detection from the extension or contents")
+            .value_parser(["csv", "kml", "gpx"]),
+        Arg::new("operator")
+            .long("operator")
+            .value_name("NAME")
+            .help("Operator name for KML records (overrides kml_operator_name)"),
+        Arg::new("since")
+            .long("since")
+            .value_name("RFC3339")
+            .help("Only upload records at or after this time"),
+        Arg::new("until")
+            .long("until")
+            .value_name("RFC3339")
+            .help("Only upload records before this time"),
+        Arg::new("bbox")
+            .long("bbox")
+            .value_name("MIN_LON,MIN_LAT,MAX_LON,MAX_LAT")
+            .help("Only upload records located inside this bounding box")
+            .allow_hyphen_values(true),
+        Arg::new("time-shift")
+            .long("time-shift")
+            .value_name("DURATION")
+            .help("Add DURATION (e.g. +72h, -30m) to every timestamp, after --since/--until")
+            .allow_hyphen_values(true),
+        Arg::new("time-now-align")
+            .long("time-now-align")
+            .help("Shift all timestamps so the newest record is at the current time")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("time-shift"),
+        Arg::new("strict-exit")
+            .long("strict-exit")
+            .help("Exit with an error after parsing if any invalid records were skipped")
+            .action(ArgAction::SetTrue),
+        Arg::new("preview")
+            .long("preview")
+            .value_name("N")
+            .help("Print the first N parsed records (default 5) as a table and exit without connecting to InfluxDB")
+            .num_args(0..=1)
+            .default_missing_value("5")
+            .value_parser(clap::value_parser!(usize)),
+        Arg::new("dedupe")
+            .long("dedupe")
+            .help("Drop records with the same timestamp, cell ID and position")
+            .action(ArgAction::SetTrue),
+        Arg::new("downsample")
+            .long("downsample")
+            .value_name("SECONDS")
+            .help("Average records into one per SECONDS-long time bucket")
+            .value_parser(clap::value_parser!(u64).range(1..)),
+        Arg::new("offset")
+            .long("offset")
+            .value_name("N")
+            .help("Skip the first N records after filtering")
+            .value_parser(clap::value_parser!(usize)),
+        Arg::new("limit")
+            .long("limit")
+            .value_name("N")
+            .help("Upload at most N records after --offset")
+            .value_parser(clap::value_parser!(usize)),
+        Arg::new("derive-distance")
+            .long("derive-distance")
+            .help("Add a distance_m field with the distance from the previous fix")
+            .action(ArgAction::SetTrue),
+        Arg::new("derive-bearing")
+            .long("derive-bearing")
+            .help("Add a bearing_deg field with the heading from the previous fix")
+            .action(ArgAction::SetTrue),
+        Arg::new("gps-track")
+            .long("gps-track")
+            .value_name("FILE")
+            .help("NMEA, GPX, KML or CSV track whose nearest fix in time sets the position of records without one"),
+        Arg::new("gps-tolerance")
+            .long("gps-tolerance")
+            .value_name("SECONDS")
+            .help("Maximum time between a record and the --gps-track fix assigned to it")
+            .value_parser(clap::value_parser!(f64))
+            .default_value("5")
+            .requires("gps-track"),
+        Arg::new("gps-override")
+            .long("gps-override")
+            .help("Replace the position of every record with the nearest --gps-track fix, not only missing ones")
+            .action(ArgAction::SetTrue)
+            .requires("gps-track"),
+        Arg::new("interpolate-gps")
+            .long("interpolate-gps")
+            .help("Fill missing coordinates between two fixes by linear interpolation")
+            .action(ArgAction::SetTrue),
+        Arg::new("classify-signal")
+            .long("classify-signal")
+            .help("Add a signal_quality field (excellent, good, fair, poor) derived from the level")
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+/// Values added to every written point, and adjustments of the points.
+fn constant_args() -> Vec<Arg> {
+    vec![
+        Arg::new("tag")
+            .long("tag")
+            .value_name("KEY=VALUE")
+            .help("Add a tag to every uploaded point (repeatable)")
+            .action(ArgAction::Append),
+        Arg::new("field")
+            .long("field")
+            .value_name("KEY=VALUE")
+            .help("Add a field to every uploaded point; numbers are written as floats (repeatable)")
+            .action(ArgAction::Append),
+        Arg::new("measurement-type")
+            .long("measurement-type")
+            .value_name("NAME")
+            .help("Value of the measurement_type tag on every point (overrides measurement_type)"),
+        Arg::new("max-cardinality")
+            .long("max-cardinality")
+            .value_name("N")
+            .help("Abort before writing if the points would form more than N series (overrides max_series_cardinality)")
+            .value_parser(clap::value_parser!(usize)),
+        Arg::new("bump-duplicate-timestamps")
+            .long("bump-duplicate-timestamps")
+            .help("Move records that share a timestamp and tags forward by one precision unit instead of overwriting")
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+fn output_args(required: bool) -> Vec<Arg> {
+    vec![
+        Arg::new("output")
+            .short('o')
+            .long("output")
+            .value_name("FILE")
+            .help("Write records to FILE instead of uploading to InfluxDB")
+            .required(required),
+        Arg::new("output-format")
+            .long("output-format")
+            .value_name("FORMAT")
+            .help("Format of the --output file")
+            .value_parser(["influx", "json", "ndjson", "geojson", "parquet"])
+            .default_value("influx"),
+    ]
+}
+
+fn upload_args() -> Vec<Arg> {
+    vec![
+        Arg::new("dry-run")
+            .long("dry-run")
+            .help("Parse the log file but don't upload to InfluxDB")
+            .action(ArgAction::SetTrue),
+        Arg::new("sqlite")
+            .long("sqlite")
+            .value_name("FILE")
+            .help("Insert the records into a SQLite database instead of uploading to InfluxDB")
+            .conflicts_with_all(["dry-run", "verify", "checkpoint", "output"]),
+        Arg::new("checkpoint")
+            .long("checkpoint")
+            .value_name("FILE")
+            .help("Skip records already uploaded according to FILE and update it after uploads")
+            .conflicts_with_all(["time-shift", "time-now-align"]),
+        Arg::new("stats")
+            .long("stats")
+            .help("Print summary statistics of the parsed records")
+            .action(ArgAction::SetTrue),
+        Arg::new("verify")
+            .long("verify")
+            .help("After uploading, count the points in the uploaded time range and compare")
+            .action(ArgAction::SetTrue),
+        Arg::new("show-recent")
+            .long("show-recent")
+            .value_name("N")
+            .help("After uploading, query and print the newest N points (default 10)")
+            .num_args(0..=1)
+            .default_missing_value("10")
+            .value_parser(clap::value_parser!(usize)),
+        Arg::new("recent-range")
+            .long("recent-range")
+            .value_name("DURATION")
+            .help("How far back --show-recent looks on InfluxDB 2.x, e.g. 30d (default: all data)")
+            .requires("show-recent"),
+        Arg::new("no-create-db")
+            .long("no-create-db")
+            .help(
+                "Don't create the database or bucket before uploading (for write-only credentials)",
+            )
+            .action(ArgAction::SetTrue),
+        Arg::new("fail-fast")
+            .long("fail-fast")
+            .help("Stop uploading at the first failed batch")
+            .action(ArgAction::SetTrue),
+        Arg::new("no-progress")
+            .long("no-progress")
+            .help("Don't show a progress bar while uploading")
+            .action(ArgAction::SetTrue),
+    ]
+}
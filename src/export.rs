@@ -0,0 +1,453 @@
+use crate::parser::GNetTrackRecord;
+use anyhow::{Result, anyhow};
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use chrono::SecondsFormat;
+use log::debug;
+use parquet::arrow::ArrowWriter;
+use rusqlite::{Connection, params};
+use serde_json::json;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+
+/// File formats supported by `--output`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Influx,
+    Json,
+    Ndjson,
+    Geojson,
+    Parquet,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "influx" => Ok(Self::Influx),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            "geojson" => Ok(Self::Geojson),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(anyhow!("Unsupported output format: {value}")),
+        }
+    }
+}
+
+/// Writes pre-formatted line protocol, one point per line.
+pub fn write_line_protocol(path: &str, lines: &[String]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for line in lines {
+        writeln!(writer, "{line}")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes all records as a single pretty-printed JSON array.
+pub fn write_json(path: &str, records: &[GNetTrackRecord]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, records)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes one compact JSON object per record and line.
+pub fn write_ndjson(path: &str, records: &[GNetTrackRecord]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for record in records {
+        serde_json::to_writer(&mut writer, record)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes records with coordinates as a GeoJSON `FeatureCollection` of `Point` features.
+/// Returns the number of features written.
+pub fn write_geojson(path: &str, records: &[GNetTrackRecord]) -> Result<usize> {
+    let features: Vec<_> = records
+        .iter()
+        .filter_map(|record| {
+            let (Some(longitude), Some(latitude)) = (record.longitude, record.latitude) else {
+                debug!(
+                    "Skipping record at {} without coordinates",
+                    record.timestamp.to_rfc3339()
+                );
+                return None;
+            };
+            Some(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [longitude, latitude],
+                },
+                "properties": {
+                    "timestamp": record.timestamp.to_rfc3339(),
+                    "level": record.level,
+                    "speed": record.speed,
+                    "network_tech": record.network_tech,
+                },
+            }))
+        })
+        .collect();
+    let count = features.len();
+
+    let collection = json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &collection)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Writes all records as a Parquet file with one column per record field. Optional fields
+/// are nullable columns, `timestamp` is a UTC microsecond timestamp, and `extra` holds the
+/// mapped columns of each record as a JSON object (null when there are none).
+pub fn write_parquet(path: &str, records: &[GNetTrackRecord]) -> Result<()> {
+    let float = |value: fn(&GNetTrackRecord) -> Option<f64>| -> ArrayRef {
+        Arc::new(records.iter().map(value).collect::<Float64Array>())
+    };
+    let text = |value: fn(&GNetTrackRecord) -> Option<&str>| -> ArrayRef {
+        Arc::new(records.iter().map(value).collect::<StringArray>())
+    };
+    let timestamps = TimestampMicrosecondArray::from_iter_values(
+        records
+            .iter()
+            .map(|record| record.timestamp.timestamp_micros()),
+    )
+    .with_timezone("UTC");
+    let gps_interpolated: BooleanArray = records
+        .iter()
+        .map(|record| Some(record.gps_interpolated))
+        .collect();
+    let extra: StringArray = records
+        .iter()
+        .map(|record| {
+            (!record.extra.is_empty())
+                .then(|| serde_json::to_string(&record.extra))
+                .transpose()
+        })
+        .collect::<Result<_, _>>()?;
+
+    let batch = RecordBatch::try_from_iter_with_nullable([
+        ("timestamp", Arc::new(timestamps) as ArrayRef, false),
+        ("longitude", float(|r| r.longitude), true),
+        ("latitude", float(|r| r.latitude), true),
+        ("altitude", float(|r| r.altitude), true),
+        ("speed", float(|r| r.speed), true),
+        ("operator_name", text(|r| r.operator_name.as_deref()), true),
+        ("operator_code", text(|r| r.operator_code.as_deref()), true),
+        ("mcc", text(|r| r.mcc.as_deref()), true),
+        ("mnc", text(|r| r.mnc.as_deref()), true),
+        ("cgi", text(|r| r.cgi.as_deref()), true),
+        ("cellname", text(|r| r.cellname.as_deref()), true),
+        ("node", text(|r| r.node.as_deref()), true),
+        ("cell_id", text(|r| r.cell_id.as_deref()), true),
+        ("lac", text(|r| r.lac.as_deref()), true),
+        ("network_tech", text(|r| r.network_tech.as_deref()), true),
+        ("network_mode", text(|r| r.network_mode.as_deref()), true),
+        ("level", float(|r| r.level), true),
+        ("qual", float(|r| r.qual), true),
+        ("snr", float(|r| r.snr), true),
+        ("cqi", float(|r| r.cqi), true),
+        ("arfcn", text(|r| r.arfcn.as_deref()), true),
+        ("dl_bitrate", float(|r| r.dl_bitrate), true),
+        ("ul_bitrate", float(|r| r.ul_bitrate), true),
+        ("ping_avg", float(|r| r.ping_avg), true),
+        ("ping_min", float(|r| r.ping_min), true),
+        ("ping_max", float(|r| r.ping_max), true),
+        ("ping_loss", float(|r| r.ping_loss), true),
+        ("distance_m", float(|r| r.distance_m), true),
+        ("bearing_deg", float(|r| r.bearing_deg), true),
+        (
+            "signal_quality",
+            text(|r| r.signal_quality.as_deref()),
+            true,
+        ),
+        (
+            "gps_interpolated",
+            Arc::new(gps_interpolated) as ArrayRef,
+            false,
+        ),
+        ("extra", Arc::new(extra) as ArrayRef, true),
+    ])?;
+
+    let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Columns of the SQLite `measurements` table, in insert order.
+const SQLITE_COLUMNS: &str = "timestamp TEXT NOT NULL,
+    longitude REAL, latitude REAL, altitude REAL, speed REAL,
+    operator_name TEXT, operator_code TEXT, mcc TEXT, mnc TEXT,
+    cgi TEXT, cellname TEXT, node TEXT, cell_id TEXT, lac TEXT,
+    network_tech TEXT, network_mode TEXT,
+    level REAL, qual REAL, snr REAL, cqi REAL, arfcn TEXT,
+    dl_bitrate REAL, ul_bitrate REAL,
+    ping_avg REAL, ping_min REAL, ping_max REAL, ping_loss REAL,
+    distance_m REAL, bearing_deg REAL, signal_quality TEXT,
+    gps_interpolated INTEGER NOT NULL, extra TEXT";
+
+/// Inserts all records into the `measurements` table of the SQLite database at `path`,
+/// creating the database, table and timestamp index as needed. Existing rows are kept.
+/// Timestamps are stored as RFC3339 UTC text with microseconds, so they sort as text, and
+/// `extra` as a JSON object (NULL when there are none).
+pub fn write_sqlite(path: &str, records: &[GNetTrackRecord]) -> Result<()> {
+    let mut connection = Connection::open(path)?;
+    connection.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS measurements ({SQLITE_COLUMNS});
+         CREATE INDEX IF NOT EXISTS measurements_timestamp ON measurements (timestamp);"
+    ))?;
+
+    let transaction = connection.transaction()?;
+    {
+        let placeholders = vec!["?"; 32].join(", ");
+        let mut insert =
+            transaction.prepare(&format!("INSERT INTO measurements VALUES ({placeholders})"))?;
+        for r in records {
+            let extra = (!r.extra.is_empty())
+                .then(|| serde_json::to_string(&r.extra))
+                .transpose()?;
+            insert.execute(params![
+                r.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+                r.longitude,
+                r.latitude,
+                r.altitude,
+                r.speed,
+                r.operator_name,
+                r.operator_code,
+                r.mcc,
+                r.mnc,
+                r.cgi,
+                r.cellname,
+                r.node,
+                r.cell_id,
+                r.lac,
+                r.network_tech,
+                r.network_mode,
+                r.level,
+                r.qual,
+                r.snr,
+                r.cqi,
+                r.arfcn,
+                r.dl_bitrate,
+                r.ul_bitrate,
+                r.ping_avg,
+                r.ping_min,
+                r.ping_max,
+                r.ping_loss,
+                r.distance_m,
+                r.bearing_deg,
+                r.signal_quality,
+                r.gps_interpolated,
+                extra,
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::record;
+    use std::fs;
+
+    #[test]
+    fn writes_one_line_per_point() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.lp");
+        let path = path.to_str().unwrap();
+        let lines = ["m,a=1 f=1 1".to_string(), "m,a=2 f=2 2".to_string()];
+
+        write_line_protocol(path, &lines).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "m,a=1 f=1 1\nm,a=2 f=2 2\n"
+        );
+    }
+
+    #[test]
+    fn ndjson_round_trips_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.ndjson");
+        let path = path.to_str().unwrap();
+        let records = [
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:09.250Z", "longitude": 139.5,
+                    "latitude": 35.7, "operator_name": "NTT docomo", "level": -95.0,
+                    "extra": {"band": "B1"}}"#,
+            ),
+            record(r#"{"timestamp": "2025-10-03T10:20:10Z", "gps_interpolated": true}"#),
+        ];
+
+        write_ndjson(path, &records).unwrap();
+
+        let content = fs::read_to_string(path).unwrap();
+        let parsed: Vec<GNetTrackRecord> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(parsed.len(), 2);
+        for (parsed, record) in parsed.iter().zip(&records) {
+            assert_eq!(
+                serde_json::to_value(parsed).unwrap(),
+                serde_json::to_value(record).unwrap()
+            );
+        }
+        assert_eq!(parsed[0].level, Some(-95.0));
+        assert_eq!(parsed[1].level, None);
+        assert_eq!(parsed[1].operator_name, None);
+        assert!(parsed[1].gps_interpolated);
+    }
+
+    #[test]
+    fn json_writes_an_array() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.json");
+        let path = path.to_str().unwrap();
+
+        write_json(path, &[record(r#"{"timestamp": "2025-10-03T10:20:09Z"}"#)]).unwrap();
+
+        let parsed: Vec<GNetTrackRecord> =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(
+            parsed[0].timestamp.to_rfc3339(),
+            "2025-10-03T10:20:09+00:00"
+        );
+    }
+
+    #[test]
+    fn geojson_has_a_feature_per_located_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.geojson");
+        let path = path.to_str().unwrap();
+        let records = [
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:09Z", "longitude": 139.5, "latitude": 35.7,
+                    "level": -95.0, "network_tech": "LTE"}"#,
+            ),
+            record(r#"{"timestamp": "2025-10-03T10:20:10Z", "level": -96.0}"#),
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:11Z", "longitude": 139.6, "latitude": 35.8}"#,
+            ),
+        ];
+
+        assert_eq!(write_geojson(path, &records).unwrap(), 2);
+
+        let collection: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(collection["type"], "FeatureCollection");
+        let features = collection["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0]["type"], "Feature");
+        assert_eq!(features[0]["geometry"]["type"], "Point");
+        assert_eq!(features[0]["geometry"]["coordinates"], json!([139.5, 35.7]));
+        assert_eq!(features[0]["properties"]["level"], -95.0);
+        assert_eq!(features[0]["properties"]["network_tech"], "LTE");
+        assert_eq!(
+            features[0]["properties"]["timestamp"],
+            "2025-10-03T10:20:09+00:00"
+        );
+        assert!(features[1]["properties"]["level"].is_null());
+    }
+
+    #[test]
+    fn parquet_reads_back_a_row_per_record() {
+        use arrow_array::Array;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.parquet");
+        let path = path.to_str().unwrap();
+        let records = [
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:09.250Z", "operator_name": "NTT docomo",
+                    "level": -95.0, "extra": {"band": "B1"}}"#,
+            ),
+            record(r#"{"timestamp": "2025-10-03T10:20:10Z"}"#),
+            record(r#"{"timestamp": "2025-10-03T10:20:11Z", "level": -97.5}"#),
+        ];
+
+        write_parquet(path, &records).unwrap();
+
+        let batches: Vec<RecordBatch> =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+                .unwrap()
+                .build()
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 3);
+        let batch = &batches[0];
+        let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+
+        let timestamps = column("timestamp");
+        let timestamps = timestamps
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(timestamps.value(0), records[0].timestamp.timestamp_micros());
+        let levels = column("level");
+        let levels = levels.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(levels.value(0), -95.0);
+        assert!(levels.is_null(1));
+        assert_eq!(levels.value(2), -97.5);
+        let operators = column("operator_name");
+        let operators = operators.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(operators.value(0), "NTT docomo");
+        assert!(operators.is_null(2));
+        let extra = column("extra");
+        let extra = extra.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(extra.value(0), r#"{"band":"B1"}"#);
+        assert!(extra.is_null(1));
+    }
+
+    #[test]
+    fn sqlite_appends_a_row_per_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.db");
+        let path = path.to_str().unwrap();
+        let records = [
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:09.250Z", "level": -95.0,
+                    "extra": {"band": "B1"}}"#,
+            ),
+            record(r#"{"timestamp": "2025-10-03T10:20:10Z", "gps_interpolated": true}"#),
+        ];
+
+        write_sqlite(path, &records).unwrap();
+        // A second run keeps the earlier rows
+        write_sqlite(path, &records[..1]).unwrap();
+
+        let connection = Connection::open(path).unwrap();
+        let count: i64 = connection
+            .query_row("SELECT COUNT(*) FROM measurements", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+        let (timestamp, level, extra, interpolated): (String, Option<f64>, Option<String>, bool) =
+            connection
+                .query_row(
+                    "SELECT timestamp, level, extra, gps_interpolated FROM measurements
+                     ORDER BY timestamp DESC LIMIT 1",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                )
+                .unwrap();
+        assert_eq!(timestamp, "2025-10-03T10:20:10.000000Z");
+        assert_eq!(level, None);
+        assert_eq!(extra, None);
+        assert!(interpolated);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::parser::GNetTrackRecord;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use log::debug;
+use std::io::{BufRead, BufReader, Read};
+
+/// One position of a GPS track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsFix {
+    pub timestamp: DateTime<Utc>,
+    pub longitude: f64,
+    pub latitude: f64,
+    pub altitude: Option<f64>,
+}
+
+/// A separately logged GPS track, sorted by time, whose fixes are attached to the radio
+/// records nearest in time.
+#[derive(Debug, Clone, Default)]
+pub struct GpsTrack {
+    fixes: Vec<GpsFix>,
+}
+
+impl GpsTrack {
+    pub fn new(mut fixes: Vec<GpsFix>) -> Self {
+        fixes.sort_by_key(|fix| fix.timestamp);
+        Self { fixes }
+    }
+
+    /// Uses the positioned records of a parsed GPX, KML or CSV track.
+    pub fn from_records(records: &[GNetTrackRecord]) -> Self {
+        Self::new(
+            records
+                .iter()
+                .filter_map(|record| {
+                    Some(GpsFix {
+                        timestamp: record.timestamp,
+                        longitude: record.longitude?,
+                        latitude: record.latitude?,
+                        altitude: record.altitude,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Reads the `RMC` sentences of an NMEA log, from any talker (`$GPRMC`, `$GNRMC`, ...).
+    /// Other sentences, fixes flagged invalid and sentences with a wrong checksum are skipped.
+    pub fn parse_nmea<R: Read>(reader: R) -> Result<Self> {
+        let mut fixes = Vec::new();
+        for (index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            match parse_rmc(line.trim()) {
+                Ok(Some(fix)) => fixes.push(fix),
+                Ok(None) => {}
+                Err(e) => debug!("Skipping NMEA line {}: {e}", index + 1),
+            }
+        }
+        Ok(Self::new(fixes))
+    }
+
+    pub fn len(&self) -> usize {
+        self.fixes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fixes.is_empty()
+    }
+
+    /// The fix nearest to `timestamp`, if it is at most `tolerance` away. The earlier fix
+    /// wins a tie.
+    pub fn nearest(&self, timestamp: DateTime<Utc>, tolerance: TimeDelta) -> Option<&GpsFix> {
+        let next = self.fixes.partition_point(|fix| fix.timestamp < timestamp);
+        let before = next.checked_sub(1).and_then(|i| self.fixes.get(i));
+        let after = self.fixes.get(next);
+        let distance = |fix: &GpsFix| (fix.timestamp - timestamp).abs();
+        match (before, after) {
+            (Some(before), Some(after)) if distance(after) < distance(before) => Some(after),
+            (Some(before), _) => Some(before),
+            (None, after) => after,
+        }
+        .filter(|fix| distance(fix) <= tolerance)
+    }
+
+    /// Sets the position of records without coordinates, or of every record with
+    /// `override_all`, to the nearest fix within `tolerance`. Returns how many were set.
+    pub fn apply(
+        &self,
+        records: &mut [GNetTrackRecord],
+        tolerance: TimeDelta,
+        override_all: bool,
+    ) -> usize {
+        let mut matched = 0;
+        for record in records.iter_mut() {
+            if !override_all && record.longitude.is_some() && record.latitude.is_some() {
+                continue;
+            }
+            if let Some(fix) = self.nearest(record.timestamp, tolerance) {
+                record.longitude = Some(fix.longitude);
+                record.latitude = Some(fix.latitude);
+                if fix.altitude.is_some() {
+                    record.altitude = fix.altitude;
+                }
+                record.gps_interpolated = false;
+                matched += 1;
+            }
+        }
+        matched
+    }
+}
+
+/// Parses an RMC sentence, e.g.
+/// `$GPRMC,102009.00,A,3541.0340,N,13945.7980,E,0.0,0.0,031025,,,A*6B`. `None` for other
+/// sentences and void fixes.
+fn parse_rmc(line: &str) -> Result<Option<GpsFix>> {
+    let Some(sentence) = line.strip_prefix('$') else {
+        return Ok(None);
+    };
+    let (data, checksum) = match sentence.split_once('*') {
+        Some((data, checksum)) => (data, Some(checksum)),
+        None => (sentence, None),
+    };
+    let fields: Vec<&str> = data.split(',').collect();
+    if fields[0].len() != 5 || !fields[0].ends_with("RMC") {
+        return Ok(None);
+    }
+    if let Some(checksum) = checksum {
+        let expected = data.bytes().fold(0u8, |sum, b| sum ^ b);
+        if u8::from_str_radix(checksum.trim(), 16).ok() != Some(expected) {
+            return Err(anyhow!("checksum mismatch"));
+        }
+    }
+    if fields.len() < 10 {
+        return Err(anyhow!("RMC sentence has only {} fields", fields.len()));
+    }
+    if fields[2] != "A" {
+        return Ok(None);
+    }
+
+    let time = NaiveTime::parse_from_str(fields[1], "%H%M%S%.f")
+        .map_err(|e| anyhow!("invalid time '{}': {e}", fields[1]))?;
+    let date = NaiveDate::parse_from_str(fields[9], "%d%m%y")
+        .map_err(|e| anyhow!("invalid date '{}': {e}", fields[9]))?;
+    let latitude = parse_coordinate(fields[3], fields[4], 'N', 'S')?;
+    let longitude = parse_coordinate(fields[5], fields[6], 'E', 'W')?;
+
+    Ok(Some(GpsFix {
+        timestamp: date.and_time(time).and_utc(),
+        longitude,
+        latitude,
+        altitude: None,
+    }))
+}
+
+/// Converts an NMEA `dddmm.mmmm` value and its hemisphere to signed decimal degrees.
+fn parse_coordinate(value: &str, hemisphere: &str, positive: char, negative: char) -> Result<f64> {
+    let raw: f64 = value
+        .parse()
+        .map_err(|_| anyhow!("invalid coordinate '{value}'"))?;
+    let degrees = (raw / 100.0).trunc() + (raw % 100.0) / 60.0;
+    match hemisphere.chars().next() {
+        Some(c) if c == positive => Ok(degrees),
+        Some(c) if c == negative => Ok(-degrees),
+        _ => Err(anyhow!("invalid hemisphere '{hemisphere}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::record;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        format!("2025-10-03T{time}Z").parse().unwrap()
+    }
+
+    fn fix(time: &str, longitude: f64) -> GpsFix {
+        GpsFix {
+            timestamp: at(time),
+            longitude,
+            latitude: 35.7,
+            altitude: None,
+        }
+    }
+
+    #[test]
+    fn reads_valid_rmc_fixes_from_nmea() {
+        let nmea = "$GNRMC,102011,A,3541.0400,S,13945.8000,W,0.0,0.0,031025,,,A*62
+$GPRMC,102009.00,A,3541.0340,N,13945.7980,E,0.0,0.0,031025,,,A*59
+$GPGGA,102009.00,3541.0340,N,13945.7980,E,1,08,1.0,40.0,M,39.0,M,,*59
+$GPRMC,102012.00,V,,,,,,,031025,,,N*78
+$GPRMC,102013.00,A,3541.0340,N,13945.7980,E,0.0,0.0,031025,,,A*00
+
+";
+        let track = GpsTrack::parse_nmea(nmea.as_bytes()).unwrap();
+
+        // Sorted by time; the GGA, void and corrupted sentences are skipped
+        assert_eq!(track.len(), 2);
+        let [first, second] = track.fixes[..] else {
+            unreachable!()
+        };
+        assert_eq!(first.timestamp, at("10:20:09"));
+        assert!((first.latitude - 35.6839).abs() < 1e-6);
+        assert!((first.longitude - 139.7633).abs() < 1e-6);
+        assert_eq!(second.timestamp, at("10:20:11"));
+        assert!((second.latitude + 35.684).abs() < 1e-6);
+        assert!((second.longitude + 139.763333).abs() < 1e-6);
+    }
+
+    #[test]
+    fn finds_the_nearest_fix_within_the_tolerance() {
+        let track = GpsTrack::new(vec![fix("10:20:10", 1.0), fix("10:20:00", 0.0)]);
+        let nearest = |time: &str, secs: i64| {
+            track
+                .nearest(at(time), TimeDelta::seconds(secs))
+                .map(|fix| fix.longitude)
+        };
+
+        assert_eq!(nearest("10:20:10", 0), Some(1.0));
+        assert_eq!(nearest("10:20:03", 5), Some(0.0));
+        assert_eq!(nearest("10:20:07", 5), Some(1.0));
+        // A tie goes to the earlier fix
+        assert_eq!(nearest("10:20:05", 5), Some(0.0));
+        assert_eq!(nearest("10:20:17", 5), None);
+        assert_eq!(nearest("10:19:50", 5), None);
+        assert_eq!(
+            GpsTrack::default().nearest(at("10:20:00"), TimeDelta::MAX),
+            None
+        );
+    }
+
+    #[test]
+    fn positions_the_records_without_coordinates() {
+        let track = GpsTrack::new(vec![fix("10:20:09", 139.5), fix("10:20:20", 139.6)]);
+        let records = [
+            record(r#"{"timestamp": "2025-10-03T10:20:09Z", "gps_interpolated": true}"#),
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:10Z", "longitude": 139.0, "latitude": 35.0,
+                    "altitude": 12.0}"#,
+            ),
+            record(r#"{"timestamp": "2025-10-03T10:20:15Z"}"#),
+        ];
+        let apply = |override_all: bool| {
+            let mut records = records.clone();
+            let matched = track.apply(&mut records, TimeDelta::seconds(2), override_all);
+            let longitudes: Vec<_> = records.iter().map(|r| r.longitude).collect();
+            (matched, longitudes, records)
+        };
+
+        let (matched, positions, records) = apply(false);
+        assert_eq!(matched, 1);
+        assert_eq!(positions, [Some(139.5), Some(139.0), None]);
+        assert_eq!(records[0].latitude, Some(35.7));
+        assert!(!records[0].gps_interpolated);
+
+        let (matched, positions, records) = apply(true);
+        assert_eq!(matched, 2);
+        assert_eq!(positions, [Some(139.5), Some(139.5), None]);
+        // Fixes without an altitude keep the record's
+        assert_eq!(records[1].altitude, Some(12.0));
+    }
+}
@@ -0,0 +1,321 @@
+use crate::config::SpeedUnit;
+use crate::input_format::open_input;
+use crate::parser::{GNetTrackRecord, ParseOutcome};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+
+pub struct GpxParser {
+    skip_invalid: bool,
+}
+
+impl GpxParser {
+    pub fn new(skip_invalid: bool) -> Self {
+        Self { skip_invalid }
+    }
+
+    pub fn parse_file(&self, file_path: &str) -> Result<Vec<GNetTrackRecord>> {
+        self.parse_reader(open_input(file_path)?)
+    }
+
+    /// Parses a GPX document from any reader.
+    pub fn parse_reader<R: Read>(&self, reader: R) -> Result<Vec<GNetTrackRecord>> {
+        Ok(self.parse_reader_outcome(reader)?.records)
+    }
+
+    /// Like `parse_reader`, also counting the invalid elements that were skipped.
+    pub fn parse_reader_outcome<R: Read>(&self, reader: R) -> Result<ParseOutcome> {
+        let buf_reader = BufReader::new(reader);
+        let mut reader = Reader::from_reader(buf_reader);
+        reader.config_mut().trim_text(true);
+
+        let mut records = Vec::new();
+        let mut buf = Vec::new();
+        let mut error_count = 0;
+
+        let mut in_trkpt = false;
+        let mut in_extensions = false;
+        let mut current_point = TrackPointData::new();
+
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) => match e.local_name().as_ref() {
+                    b"trkpt" => {
+                        in_trkpt = true;
+                        current_point = TrackPointData::from_attributes(e);
+                    }
+                    b"extensions" if in_trkpt => in_extensions = true,
+                    b"ele" if in_trkpt && !in_extensions => {
+                        let mut text_buf = Vec::new();
+                        current_point.elevation =
+                            Some(self.read_text_content(&mut reader, &mut text_buf)?);
+                    }
+                    b"time" if in_trkpt && !in_extensions => {
+                        let mut text_buf = Vec::new();
+                        current_point.time =
+                            Some(self.read_text_content(&mut reader, &mut text_buf)?);
+                    }
+                    name if in_extensions => {
+                        let name = String::from_utf8_lossy(name).to_lowercase();
+                        if TrackPointData::is_extension_field(&name) {
+                            let mut text_buf = Vec::new();
+                            let value = self.read_text_content(&mut reader, &mut text_buf)?;
+                            current_point.add_extension(&name, &value);
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::Empty(ref e)) if e.local_name().as_ref() == b"trkpt" => {
+                    let point = TrackPointData::from_attributes(e);
+                    self.push_point(&point, &mut records, &mut error_count)?;
+                }
+                Ok(Event::End(ref e)) => match e.local_name().as_ref() {
+                    b"trkpt" if in_trkpt => {
+                        self.push_point(&current_point, &mut records, &mut error_count)?;
+                        in_trkpt = false;
+                        in_extensions = false;
+                    }
+                    b"extensions" => in_extensions = false,
+                    _ => {}
+                },
+                Ok(Event::Eof) => break,
+                Err(e) => {
+                    error_count += 1;
+                    if self.skip_invalid {
+                        warn!("XML parsing error: {e}");
+                    } else {
+                        return Err(anyhow!("XML parsing error: {e}"));
+                    }
+                }
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        if error_count > 0 {
+            warn!("Encountered {error_count} errors while parsing GPX file");
+        }
+
+        debug!("Parsed {} track points from GPX file", records.len());
+        Ok(ParseOutcome {
+            records,
+            skipped: error_count,
+        })
+    }
+
+    fn push_point(
+        &self,
+        point: &TrackPointData,
+        records: &mut Vec<GNetTrackRecord>,
+        error_count: &mut usize,
+    ) -> Result<()> {
+        match point.to_record() {
+            Ok(record) => records.push(record),
+            Err(e) => {
+                *error_count += 1;
+                if self.skip_invalid {
+                    warn!("Skipping invalid track point: {e}");
+                } else {
+                    return Err(anyhow!("Error parsing track point: {e}"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_text_content<R: BufRead>(
+        &self,
+        reader: &mut Reader<R>,
+        buf: &mut Vec<u8>,
+    ) -> Result<String> {
+        let mut content = String::new();
+        loop {
+            buf.clear();
+            match reader.read_event_into(buf) {
+                Ok(Event::Text(e)) => {
+                    content.push_str(&e.unescape().unwrap_or_default());
+                }
+                Ok(Event::End(_)) => break,
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(anyhow!("Error reading text content: {e}")),
+                _ => {}
+            }
+        }
+        Ok(content)
+    }
+}
+
+#[derive(Debug, Default)]
+struct TrackPointData {
+    lat: Option<String>,
+    lon: Option<String>,
+    elevation: Option<String>,
+    time: Option<String>,
+    level: Option<String>,
+    speed: Option<String>,
+}
+
+impl TrackPointData {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn from_attributes(element: &BytesStart) -> Self {
+        let mut point = Self::new();
+        for attr in element.attributes().flatten() {
+            let value = String::from_utf8_lossy(&attr.value).to_string();
+            match attr.key.local_name().as_ref() {
+                b"lat" => point.lat = Some(value),
+                b"lon" => point.lon = Some(value),
+                _ => {}
+            }
+        }
+        point
+    }
+
+    fn is_extension_field(name: &str) -> bool {
+        matches!(name, "speed" | "level" | "rsrp" | "signal")
+    }
+
+    fn add_extension(&mut self, name: &str, value: &str) {
+        match name {
+            "speed" => self.speed = Some(value.to_string()),
+            "level" | "rsrp" | "signal" => self.level = Some(value.to_string()),
+            _ => {
+                debug!("Unknown GPX extension field: {name}");
+            }
+        }
+    }
+
+    fn to_record(&self) -> Result<GNetTrackRecord> {
+        let latitude = parse_coordinate(self.lat.as_deref(), "lat")?;
+        let longitude = parse_coordinate(self.lon.as_deref(), "lon")?;
+
+        let timestamp = if let Some(ref time_str) = self.time {
+            DateTime::parse_from_rfc3339(time_str.trim())
+                .map(|dt| dt.with_timezone(&Utc))
+                .map_err(|e| anyhow!("Unable to parse GPX timestamp '{time_str}': {e}"))?
+        } else {
+            Utc::now()
+        };
+
+        let altitude = self
+            .elevation
+            .as_ref()
+            .and_then(|ele| ele.trim().parse::<f64>().ok());
+
+        // GPX speeds are in m/s; records store km/h like the KML parser
+        let speed = self
+            .speed
+            .as_ref()
+            .and_then(|speed| speed.trim().parse::<f64>().ok())
+            .map(|mps| SpeedUnit::Ms.to_kmh(mps));
+
+        let level = self
+            .level
+            .as_ref()
+            .and_then(|level| level.replace("dBm", "").trim().parse::<f64>().ok());
+
+        Ok(GNetTrackRecord {
+            timestamp,
+            longitude,
+            latitude,
+            altitude,
+            speed,
+            operator_name: None,
+            operator_code: None,
+            mcc: None,
+            mnc: None,
+            cgi: None,
+            cellname: None,
+            node: None,
+            cell_id: None,
+            lac: None,
+            network_tech: None,
+            network_mode: None,
+            level,
+            qual: None,
+            snr: None,
+            cqi: None,
+            arfcn: None,
+            dl_bitrate: None,
+            ul_bitrate: None,
+            ping_avg: None,
+            ping_min: None,
+            ping_max: None,
+            ping_loss: None,
+            distance_m: None,
+            bearing_deg: None,
+            signal_quality: None,
+            gps_interpolated: false,
+            extra: HashMap::new(),
+        })
+    }
+}
+
+fn parse_coordinate(value: Option<&str>, name: &str) -> Result<Option<f64>> {
+    match value {
+        Some(value) => value
+            .trim()
+            .parse::<f64>()
+            .map(Some)
+            .map_err(|_| anyhow!("Invalid {name} attribute: {value}")),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk><trkseg>
+    <trkpt lat="35.681236" lon="139.767125">
+      <ele>40.5</ele>
+      <time>2025-10-03T10:20:09Z</time>
+      <extensions><speed>10</speed><rsrp>-95 dBm</rsrp></extensions>
+    </trkpt>
+    <trkpt lat="35.6812" lon="139.7672"/>
+    <trkpt lat="35.6813" lon="139.7673"><time>2025-10-03T19:20:11+09:00</time></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+
+    #[test]
+    fn parses_track_points() {
+        let records = GpxParser::new(false).parse_reader(GPX.as_bytes()).unwrap();
+        assert_eq!(records.len(), 3);
+
+        let first = &records[0];
+        assert_eq!(first.latitude, Some(35.681236));
+        assert_eq!(first.longitude, Some(139.767125));
+        assert_eq!(first.altitude, Some(40.5));
+        assert_eq!(first.timestamp.to_rfc3339(), "2025-10-03T10:20:09+00:00");
+        assert_eq!(first.speed, Some(36.0));
+        assert_eq!(first.level, Some(-95.0));
+
+        assert_eq!(records[1].latitude, Some(35.6812));
+        assert_eq!(records[1].longitude, Some(139.7672));
+        assert_eq!(
+            records[2].timestamp.to_rfc3339(),
+            "2025-10-03T10:20:11+00:00"
+        );
+    }
+
+    #[test]
+    fn skips_invalid_coordinates() {
+        let gpx = r#"<gpx><trk><trkseg>
+            <trkpt lat="north" lon="139.7"/><trkpt lat="35.6" lon="139.7"/>
+        </trkseg></trk></gpx>"#;
+        let outcome = GpxParser::new(true)
+            .parse_reader_outcome(gpx.as_bytes())
+            .unwrap();
+        assert_eq!(outcome.records.len(), 1);
+        assert_eq!(outcome.skipped, 1);
+        assert!(GpxParser::new(false).parse_reader(gpx.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::config::InfluxDbConfig;
+use crate::influx_client::http_client_builder;
+use crate::redact::redact_url;
+use anyhow::{Result, anyhow};
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Number of leading bytes inspected when sniffing a file's format.
+const SNIFF_LEN: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Csv,
+    Kml,
+    Gpx,
+}
+
+impl InputFormat {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "csv" => Ok(InputFormat::Csv),
+            "kml" => Ok(InputFormat::Kml),
+            "gpx" => Ok(InputFormat::Gpx),
+            other => Err(anyhow!("Unsupported input format: {other}")),
+        }
+    }
+
+    /// Returns the format implied by an unambiguous file extension, if any. A trailing `.gz`
+    /// is skipped, so `drive.csv.gz` is CSV.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let path = if is_gzip(path) {
+            Path::new(path.file_stem()?)
+        } else {
+            path
+        };
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "csv" => Some(InputFormat::Csv),
+            "kml" => Some(InputFormat::Kml),
+            "gpx" => Some(InputFormat::Gpx),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `path` has a `.gz` extension.
+pub fn is_gzip(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+}
+
+/// Opens an input file, decompressing it on the fly when it has a `.gz` extension.
+pub fn open_input(path: impl AsRef<Path>) -> Result<Box<dyn Read>> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    if is_gzip(path) {
+        Ok(Box::new(GzDecoder::new(file)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Whether an `--input` value is an `http://` or `https://` URL rather than a path.
+pub fn is_url(input: &str) -> bool {
+    let lower = input.to_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// Downloads a log file with the same proxy and timeout settings as the InfluxDB client.
+/// The body is decompressed if the URL path ends in `.gz` or the body is gzip data.
+pub async fn download(url: &str, config: &InfluxDbConfig) -> Result<Vec<u8>> {
+    let client = http_client_builder(config)?.build()?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to download {}: {e}", redact_url(url)))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow!(
+            "Failed to download {}: server returned {status}",
+            redact_url(url)
+        ));
+    }
+
+    let body = response.bytes().await?;
+    let gzip = url_path(url).is_some_and(|path| is_gzip(Path::new(&path)))
+        || body.starts_with(&[0x1f, 0x8b]);
+    if !gzip {
+        return Ok(body.to_vec());
+    }
+    let mut decompressed = Vec::new();
+    GzDecoder::new(body.as_ref())
+        .read_to_end(&mut decompressed)
+        .map_err(|e| anyhow!("Failed to decompress {}: {e}", redact_url(url)))?;
+    Ok(decompressed)
+}
+
+/// Path component of a URL, for picking the format from its extension.
+pub fn url_path(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .map(|url| url.path().to_string())
+}
+
+/// Picks the parser for `path` from its contents: XML documents with a `<gpx` root are GPX,
+/// other XML (`<?xml` or `<kml`) is KML, and anything else is treated as CSV.
+pub fn detect_format(path: &Path) -> Result<InputFormat> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    open_input(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut buf)?;
+    Ok(sniff_format(&buf))
+}
+
+/// Picks the parser from the leading bytes of an input, as `detect_format` does for files.
+pub fn sniff_format(bytes: &[u8]) -> InputFormat {
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let head = String::from_utf8_lossy(&bytes[start..]).to_lowercase();
+
+    if !head.starts_with('<') {
+        return InputFormat::Csv;
+    }
+    if head.starts_with("<gpx") || (head.starts_with("<?xml") && head.contains("<gpx")) {
+        InputFormat::Gpx
+    } else if head.starts_with("<kml") || head.starts_with("<?xml") {
+        InputFormat::Kml
+    } else {
+        InputFormat::Csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn sniffs_each_format() {
+        assert_eq!(sniff_format(b"Timestamp,Level\n"), InputFormat::Csv);
+        assert_eq!(
+            sniff_format(b"<?xml version=\"1.0\"?>\n<kml xmlns=\"...\">"),
+            InputFormat::Kml
+        );
+        assert_eq!(sniff_format(b"  <kml>"), InputFormat::Kml);
+        assert_eq!(
+            sniff_format(b"\xef\xbb\xbf<?xml version=\"1.0\"?><gpx version=\"1.1\">"),
+            InputFormat::Gpx
+        );
+        assert_eq!(sniff_format(b"<gpx>"), InputFormat::Gpx);
+        assert_eq!(sniff_format(b"<html>"), InputFormat::Csv);
+    }
+
+    #[test]
+    fn detects_kml_behind_a_misleading_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("drive.txt");
+        fs::write(&path, "<?xml version=\"1.0\"?>\n<kml><Document/></kml>\n").unwrap();
+
+        assert_eq!(InputFormat::from_extension(&path), None);
+        assert_eq!(detect_format(&path).unwrap(), InputFormat::Kml);
+    }
+
+    #[test]
+    fn picks_formats_from_extensions() {
+        assert_eq!(
+            InputFormat::from_extension(Path::new("drive.CSV")),
+            Some(InputFormat::Csv)
+        );
+        assert_eq!(
+            InputFormat::from_extension(Path::new("drive.kml.gz")),
+            Some(InputFormat::Kml)
+        );
+        assert_eq!(InputFormat::from_extension(Path::new("drive.gz")), None);
+    }
+
+    #[tokio::test]
+    async fn downloads_url_inputs() {
+        use crate::config::Config;
+        use crate::parser::{Delimiter, LogParser};
+        use crate::test_support::MockServer;
+
+        let csv = "Timestamp,Level\n2025-10-03 10:20:09,-95\n";
+        let server = MockServer::start(move |request| match request.target.as_str() {
+            "/logs/drive.csv" => (200, csv.to_string()),
+            _ => (404, String::new()),
+        })
+        .await;
+        let config = Config::default().influxdb.remove(0);
+
+        let body = download(&format!("{}/logs/drive.csv", server.url), &config)
+            .await
+            .unwrap();
+        assert_eq!(sniff_format(&body), InputFormat::Csv);
+        let records = LogParser::new(1000, false)
+            .parse_reader(body.as_slice(), Delimiter::Auto)
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].level, Some(-95.0));
+
+        let error = download(&format!("{}/logs/missing.csv", server.url), &config)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("server returned 404"), "{error}");
+    }
+}
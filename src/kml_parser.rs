@@ -1,24 +1,53 @@
-use crate::parser::GNetTrackRecord;
+use crate::input_format::open_input;
+use crate::parser::{GNetTrackRecord, ParseOutcome, local_to_utc, utc_offset};
 use anyhow::{Result, anyhow};
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
 use log::{debug, warn};
 use quick_xml::Reader;
 use quick_xml::events::Event;
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
 
 pub struct KmlParser {
     skip_invalid: bool,
+    timezone: FixedOffset,
+    operator_name: Option<String>,
 }
 
 impl KmlParser {
     pub fn new(skip_invalid: bool) -> Self {
-        Self { skip_invalid }
+        Self {
+            skip_invalid,
+            timezone: utc_offset(),
+            operator_name: None,
+        }
+    }
+
+    /// Sets the timezone that the placemark times are recorded in.
+    pub fn with_timezone(mut self, timezone: FixedOffset) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Sets the operator written to every record. KML exports don't name the operator, so it
+    /// is left empty when unset.
+    pub fn with_operator_name(mut self, operator_name: Option<String>) -> Self {
+        self.operator_name = operator_name;
+        self
     }
 
     pub fn parse_file(&self, file_path: &str) -> Result<Vec<GNetTrackRecord>> {
-        let file = File::open(file_path)?;
-        let buf_reader = BufReader::new(file);
+        self.parse_reader(open_input(file_path)?)
+    }
+
+    /// Parses a KML document from any reader.
+    pub fn parse_reader<R: Read>(&self, reader: R) -> Result<Vec<GNetTrackRecord>> {
+        Ok(self.parse_reader_outcome(reader)?.records)
+    }
+
+    /// Like `parse_reader`, also counting the invalid elements that were skipped.
+    pub fn parse_reader_outcome<R: Read>(&self, reader: R) -> Result<ParseOutcome> {
+        let buf_reader = BufReader::new(reader);
         let mut reader = Reader::from_reader(buf_reader);
         reader.config_mut().trim_text(true);
 
@@ -27,6 +56,9 @@ impl KmlParser {
         let mut error_count = 0;
 
         let mut in_placemark = false;
+        let mut in_track = false;
+        // Inside a `<TimeStamp>` or `<TimeSpan>` of the placemark
+        let mut in_time_primitive = false;
         let mut current_placemark = PlacemarkData::new();
 
         loop {
@@ -44,32 +76,53 @@ impl KmlParser {
                             current_placemark.add_data(name_str.as_ref(), &value);
                         }
                     }
-                    b"coordinates" => {
-                        if in_placemark {
-                            let mut coord_buf = Vec::new();
-                            let coords = self.read_text_content(&mut reader, &mut coord_buf)?;
-                            current_placemark.set_coordinates(&coords);
-                        }
+                    b"gx:Track" if in_placemark => in_track = true,
+                    b"TimeStamp" | b"TimeSpan" if in_placemark => in_time_primitive = true,
+                    b"when" | b"begin" if in_time_primitive => {
+                        let mut when_buf = Vec::new();
+                        let when = self.read_text_content(&mut reader, &mut when_buf)?;
+                        current_placemark.set_when(&when);
+                    }
+                    b"when" if in_track => {
+                        let mut when_buf = Vec::new();
+                        let when = self.read_text_content(&mut reader, &mut when_buf)?;
+                        current_placemark.add_track_when(&when);
+                    }
+                    b"gx:coord" if in_track => {
+                        let mut coord_buf = Vec::new();
+                        let coord = self.read_text_content(&mut reader, &mut coord_buf)?;
+                        current_placemark.add_track_coord(&coord);
+                    }
+                    b"coordinates" if in_placemark => {
+                        let mut coord_buf = Vec::new();
+                        let coords = self.read_text_content(&mut reader, &mut coord_buf)?;
+                        current_placemark.set_coordinates(&coords);
                     }
                     _ => {}
                 },
-                Ok(Event::End(ref e)) => {
-                    if e.name().as_ref() == b"Placemark" && in_placemark {
-                        match current_placemark.to_record() {
-                            Ok(record) => {
-                                records.push(record);
-                            }
-                            Err(e) => {
-                                error_count += 1;
-                                if self.skip_invalid {
-                                    warn!("Skipping invalid placemark: {e}");
-                                } else {
-                                    return Err(anyhow!("Error parsing placemark: {e}"));
-                                }
+                Ok(Event::End(ref e)) if e.name().as_ref() == b"gx:Track" => in_track = false,
+                Ok(Event::End(ref e))
+                    if matches!(e.name().as_ref(), b"TimeStamp" | b"TimeSpan") =>
+                {
+                    in_time_primitive = false
+                }
+                Ok(Event::End(ref e)) if e.name().as_ref() == b"Placemark" && in_placemark => {
+                    match current_placemark
+                        .to_records(&self.timezone, self.operator_name.as_deref())
+                    {
+                        Ok(placemark_records) => {
+                            records.extend(placemark_records);
+                        }
+                        Err(e) => {
+                            error_count += 1;
+                            if self.skip_invalid {
+                                warn!("Skipping invalid placemark: {e}");
+                            } else {
+                                return Err(anyhow!("Error parsing placemark: {e}"));
                             }
                         }
-                        in_placemark = false;
                     }
+                    in_placemark = false;
                 }
                 Ok(Event::Eof) => break,
                 Err(e) => {
@@ -89,13 +142,16 @@ impl KmlParser {
             warn!("Encountered {error_count} errors while parsing KML file");
         }
 
-        debug!("Parsed {} placemarks from KML file", records.len());
-        Ok(records)
+        debug!("Parsed {} records from KML file", records.len());
+        Ok(ParseOutcome {
+            records,
+            skipped: error_count,
+        })
     }
 
-    fn read_data_value(
+    fn read_data_value<R: BufRead>(
         &self,
-        reader: &mut Reader<BufReader<File>>,
+        reader: &mut Reader<R>,
         buf: &mut Vec<u8>,
     ) -> Result<String> {
         loop {
@@ -115,9 +171,9 @@ impl KmlParser {
         }
     }
 
-    fn read_text_content(
+    fn read_text_content<R: BufRead>(
         &self,
-        reader: &mut Reader<BufReader<File>>,
+        reader: &mut Reader<R>,
         buf: &mut Vec<u8>,
     ) -> Result<String> {
         let mut content = String::new();
@@ -144,7 +200,17 @@ struct PlacemarkData {
     speed: Option<String>,
     altitude: Option<String>,
     time: Option<String>,
+    /// `<when>` of a `<TimeStamp>` or `<begin>` of a `<TimeSpan>`, preferred over `time`
+    when: Option<String>,
+    cell_id: Option<String>,
+    lac: Option<String>,
+    cgi: Option<String>,
+    operator_code: Option<String>,
+    network_mode: Option<String>,
     coordinates: Option<String>,
+    /// `<when>` and `<gx:coord>` values of a gx:Track, in document order
+    track_when: Vec<String>,
+    track_coords: Vec<String>,
 }
 
 impl PlacemarkData {
@@ -153,44 +219,134 @@ impl PlacemarkData {
     }
 
     fn add_data(&mut self, name: &str, value: &str) {
-        match name {
-            "技術" => self.technology = Some(value.to_string()),
-            "RSRP" => self.rsrp = Some(value.to_string()),
-            "速度" => self.speed = Some(value.to_string()),
-            "高度" => self.altitude = Some(value.to_string()),
-            "時間" => self.time = Some(value.to_string()),
+        match normalize_field_name(name).as_str() {
+            "技術" | "technology" | "tech" | "networktech" | "networktype" => {
+                self.technology = Some(value.to_string())
+            }
+            "rsrp" | "level" | "signal" | "signallevel" => self.rsrp = Some(value.to_string()),
+            "速度" | "speed" => self.speed = Some(value.to_string()),
+            "高度" | "altitude" | "alt" | "elevation" => self.altitude = Some(value.to_string()),
+            "時間" | "time" | "timestamp" | "date" => self.time = Some(value.to_string()),
+            "セルid" | "cellid" => self.cell_id = Some(value.to_string()),
+            "lac" | "tac" => self.lac = Some(value.to_string()),
+            "cgi" => self.cgi = Some(value.to_string()),
+            "mccmnc" | "operatorcode" => self.operator_code = Some(value.to_string()),
+            "モード" | "mode" | "networkmode" => self.network_mode = Some(value.to_string()),
             _ => {
                 debug!("Unknown KML data field: {name}");
             }
         }
     }
 
+    fn set_when(&mut self, when: &str) {
+        self.when = Some(when.to_string());
+    }
+
+    fn add_track_when(&mut self, when: &str) {
+        self.track_when.push(when.to_string());
+    }
+
+    fn add_track_coord(&mut self, coord: &str) {
+        self.track_coords.push(coord.to_string());
+    }
+
     fn set_coordinates(&mut self, coords: &str) {
         self.coordinates = Some(coords.to_string());
     }
 
-    fn to_record(&self) -> Result<GNetTrackRecord> {
-        // Parse coordinates (longitude,latitude,altitude)
-        let (longitude, latitude) = if let Some(ref coords) = self.coordinates {
-            let parts: Vec<&str> = coords.trim().split(',').collect();
-            if parts.len() >= 2 {
-                let lon = parts[0].parse::<f64>().ok();
-                let lat = parts[1].parse::<f64>().ok();
-                (lon, lat)
-            } else {
-                (None, None)
-            }
-        } else {
-            (None, None)
-        };
+    /// Converts the placemark into records: one per `<when>`/`<gx:coord>` pair for a
+    /// `gx:Track`, otherwise one per coordinate tuple (several for a LineString).
+    fn to_records(
+        &self,
+        timezone: &FixedOffset,
+        operator_name: Option<&str>,
+    ) -> Result<Vec<GNetTrackRecord>> {
+        if self.track_when.is_empty() && self.track_coords.is_empty() {
+            return self.to_vertex_records(timezone, operator_name);
+        }
+
+        if self.track_when.len() != self.track_coords.len() {
+            warn!(
+                "gx:Track has {} <when> and {} <gx:coord> elements, skipping the unpaired ones",
+                self.track_when.len(),
+                self.track_coords.len()
+            );
+        }
+
+        self.track_when
+            .iter()
+            .zip(&self.track_coords)
+            .map(|(when, coord)| {
+                let (longitude, latitude, altitude) = parse_coordinate_tuple(coord, ' ');
+                let timestamp = parse_track_when(when, timezone)?;
+                Ok(self.build_record(longitude, latitude, altitude, timestamp, operator_name))
+            })
+            .collect()
+    }
 
+    /// Emits a record per whitespace-separated `lon,lat[,alt]` tuple in `<coordinates>`, all
+    /// sharing the placemark's time and ExtendedData. A placemark without coordinates still
+    /// yields one record.
+    fn to_vertex_records(
+        &self,
+        timezone: &FixedOffset,
+        operator_name: Option<&str>,
+    ) -> Result<Vec<GNetTrackRecord>> {
         // Parse timestamp
-        let timestamp = if let Some(ref time_str) = self.time {
-            parse_kml_timestamp(time_str)?
+        let timestamp = if let Some(ref when) = self.when {
+            parse_track_when(when, timezone)?
+        } else if let Some(ref time_str) = self.time {
+            parse_kml_timestamp(time_str, timezone)?
         } else {
             Utc::now()
         };
 
+        let tuples: Vec<&str> = self
+            .coordinates
+            .as_deref()
+            .map(|coords| coords.split_whitespace().collect())
+            .unwrap_or_default();
+        if tuples.is_empty() {
+            return Ok(vec![self.build_record(
+                None,
+                None,
+                None,
+                timestamp,
+                operator_name,
+            )]);
+        }
+
+        let is_path = tuples.len() > 1;
+        Ok(tuples
+            .into_iter()
+            .map(|tuple| {
+                // Parse coordinates (longitude,latitude,altitude)
+                let (longitude, latitude, coord_altitude) = parse_coordinate_tuple(tuple, ',');
+                let mut record = self.build_record(
+                    longitude,
+                    latitude,
+                    coord_altitude,
+                    timestamp,
+                    operator_name,
+                );
+                // A single ExtendedData altitude can't describe every vertex of a path
+                if is_path && coord_altitude.is_some() {
+                    record.altitude = coord_altitude;
+                }
+                record
+            })
+            .collect())
+    }
+
+    /// Builds a record at the given position, filling the rest from the ExtendedData.
+    fn build_record(
+        &self,
+        longitude: Option<f64>,
+        latitude: Option<f64>,
+        coord_altitude: Option<f64>,
+        timestamp: DateTime<Utc>,
+        operator_name: Option<&str>,
+    ) -> GNetTrackRecord {
         // Parse speed (remove "km/h" suffix)
         let speed = if let Some(ref speed_str) = self.speed {
             speed_str
@@ -215,27 +371,31 @@ impl PlacemarkData {
             None
         };
 
-        // Parse altitude from ExtendedData (remove "m" suffix)
-        let _altitude_parsed = if let Some(ref alt_str) = self.altitude {
-            alt_str.replace("m", "").trim().parse::<f64>().ok()
-        } else {
-            None
-        };
+        // Parse altitude from ExtendedData (remove "m" suffix), falling back to the
+        // third component of the coordinates
+        let altitude = self
+            .altitude
+            .as_ref()
+            .and_then(|alt_str| alt_str.replace("m", "").trim().parse::<f64>().ok())
+            .or(coord_altitude);
 
-        Ok(GNetTrackRecord {
+        GNetTrackRecord {
             timestamp,
             longitude,
             latitude,
+            altitude,
             speed,
-            operator_name: Some("KDDI".to_string()), // Inferred from filename
-            operator_code: None,
-            cgi: None,
+            operator_name: operator_name.map(str::to_string),
+            operator_code: self.operator_code.clone(),
+            mcc: None,
+            mnc: None,
+            cgi: self.cgi.clone(),
             cellname: None,
             node: None,
-            cell_id: None,
-            lac: None,
+            cell_id: self.cell_id.clone(),
+            lac: self.lac.clone(),
             network_tech: self.technology.clone(),
-            network_mode: None,
+            network_mode: self.network_mode.clone(),
             level,
             qual: None,
             snr: None,
@@ -243,11 +403,59 @@ impl PlacemarkData {
             arfcn: None,
             dl_bitrate: None,
             ul_bitrate: None,
-        })
+            ping_avg: None,
+            ping_min: None,
+            ping_max: None,
+            ping_loss: None,
+            distance_m: None,
+            bearing_deg: None,
+            signal_quality: None,
+            gps_interpolated: false,
+            extra: HashMap::new(),
+        }
     }
 }
 
-fn parse_kml_timestamp(time_str: &str) -> Result<DateTime<Utc>> {
+/// Splits a `lon<sep>lat[<sep>alt]` tuple. `<coordinates>` uses commas, `<gx:coord>` spaces.
+fn parse_coordinate_tuple(tuple: &str, separator: char) -> (Option<f64>, Option<f64>, Option<f64>) {
+    let parts: Vec<&str> = tuple
+        .trim()
+        .split(separator)
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.len() < 2 {
+        return (None, None, None);
+    }
+    let lon = parts[0].trim().parse::<f64>().ok();
+    let lat = parts[1].trim().parse::<f64>().ok();
+    let alt = parts.get(2).and_then(|alt| alt.trim().parse::<f64>().ok());
+    (lon, lat, alt)
+}
+
+/// Parses a gx:Track or `<TimeStamp>` `<when>`, which is an ISO 8601 time with or without
+/// an offset.
+fn parse_track_when(when: &str, timezone: &FixedOffset) -> Result<DateTime<Utc>> {
+    let when = when.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(when) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    match NaiveDateTime::parse_from_str(when, "%Y-%m-%dT%H:%M:%S%.f") {
+        Ok(naive_dt) => local_to_utc(naive_dt, timezone),
+        Err(_) => parse_kml_timestamp(when, timezone),
+    }
+}
+
+/// Normalizes an ExtendedData name so that the English UI variants ("Network Tech",
+/// "network_tech", "SPEED") match one key. Japanese names pass through unchanged.
+fn normalize_field_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '_' && *c != '-')
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn parse_kml_timestamp(time_str: &str, timezone: &FixedOffset) -> Result<DateTime<Utc>> {
     // Expected format: "2025.10.03_10.20.09"
     let cleaned = time_str.replace('_', " ");
 
@@ -260,9 +468,192 @@ fn parse_kml_timestamp(time_str: &str) -> Result<DateTime<Utc>> {
 
     for format in &formats {
         if let Ok(naive_dt) = NaiveDateTime::parse_from_str(&cleaned, format) {
-            return Ok(DateTime::from_naive_utc_and_offset(naive_dt, Utc));
+            return local_to_utc(naive_dt, timezone);
         }
     }
 
     Err(anyhow!("Unable to parse KML timestamp: {time_str}"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A KML document with the given placemarks.
+    fn kml(placemarks: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2" xmlns:gx="http://www.google.com/kml/ext/2.2">
+<Document>{placemarks}</Document>
+</kml>"#
+        )
+    }
+
+    fn parse(placemarks: &str) -> Vec<GNetTrackRecord> {
+        KmlParser::new(false)
+            .parse_reader(kml(placemarks).as_bytes())
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_altitude_from_extended_data_and_coordinates() {
+        let records = parse(
+            r#"<Placemark>
+  <ExtendedData>
+    <Data name="時間"><value>2025.10.03_10.20.09</value></Data>
+    <Data name="高度"><value>35 m</value></Data>
+  </ExtendedData>
+  <Point><coordinates>139.5,35.7,20</coordinates></Point>
+</Placemark>
+<Placemark>
+  <ExtendedData><Data name="時間"><value>2025.10.03_10.20.10</value></Data></ExtendedData>
+  <Point><coordinates>139.5,35.7,20</coordinates></Point>
+</Placemark>"#,
+        );
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].altitude, Some(35.0));
+        assert_eq!(records[1].altitude, Some(20.0));
+    }
+
+    #[test]
+    fn writes_the_configured_operator_name() {
+        let placemark = r#"<Placemark>
+  <ExtendedData><Data name="時間"><value>2025.10.03_10.20.09</value></Data></ExtendedData>
+  <Point><coordinates>139.5,35.7</coordinates></Point>
+</Placemark>"#;
+        assert_eq!(parse(placemark)[0].operator_name, None);
+
+        let records = KmlParser::new(false)
+            .with_operator_name(Some("NTT docomo".to_string()))
+            .parse_reader(kml(placemark).as_bytes())
+            .unwrap();
+        assert_eq!(records[0].operator_name.as_deref(), Some("NTT docomo"));
+    }
+
+    #[test]
+    fn reads_gx_tracks() {
+        let records = parse(
+            r#"<Placemark>
+  <ExtendedData><Data name="RSRP"><value>-95 dBm</value></Data></ExtendedData>
+  <gx:Track>
+    <when>2025-10-03T10:20:09Z</when>
+    <when>2025-10-03T10:20:10Z</when>
+    <when>2025-10-03T10:20:11Z</when>
+    <gx:coord>139.5 35.7 20</gx:coord>
+    <gx:coord>139.6 35.8 21</gx:coord>
+  </gx:Track>
+</Placemark>"#,
+        );
+        // The third <when> has no coordinate and is dropped
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].timestamp,
+            "2025-10-03T10:20:09Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(
+            records[1].timestamp,
+            "2025-10-03T10:20:10Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(
+            (
+                records[1].longitude,
+                records[1].latitude,
+                records[1].altitude
+            ),
+            (Some(139.6), Some(35.8), Some(21.0))
+        );
+        assert!(records.iter().all(|record| record.level == Some(-95.0)));
+    }
+
+    #[test]
+    fn emits_a_record_per_line_string_vertex() {
+        let records = parse(
+            r#"<Placemark>
+  <ExtendedData>
+    <Data name="時間"><value>2025.10.03_10.20.09</value></Data>
+    <Data name="高度"><value>35 m</value></Data>
+  </ExtendedData>
+  <LineString><coordinates>
+    139.5,35.7,20 139.6,35.8,21
+    139.7,35.9
+  </coordinates></LineString>
+</Placemark>"#,
+        );
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records.iter().map(|r| r.longitude).collect::<Vec<_>>(),
+            [Some(139.5), Some(139.6), Some(139.7)]
+        );
+        // Vertex altitudes win over the placemark's
+        assert_eq!(
+            records.iter().map(|r| r.altitude).collect::<Vec<_>>(),
+            [Some(20.0), Some(21.0), Some(35.0)]
+        );
+        assert!(records.iter().all(|r| r.timestamp == records[0].timestamp));
+    }
+
+    #[test]
+    fn reads_cell_fields_from_extended_data() {
+        let records = parse(
+            r#"<Placemark>
+  <ExtendedData>
+    <Data name="時間"><value>2025.10.03_10.20.09</value></Data>
+    <Data name="CellID"><value>12345</value></Data>
+    <Data name="LAC"><value>678</value></Data>
+    <Data name="CGI"><value>440-10-678-12345</value></Data>
+    <Data name="MCC-MNC"><value>44010</value></Data>
+    <Data name="Mode"><value>FDD</value></Data>
+  </ExtendedData>
+  <Point><coordinates>139.5,35.7</coordinates></Point>
+</Placemark>
+<Placemark>
+  <ExtendedData>
+    <Data name="時間"><value>2025.10.03_10.20.10</value></Data>
+    <Data name="セルID"><value>54321</value></Data>
+    <Data name="モード"><value>TDD</value></Data>
+  </ExtendedData>
+  <Point><coordinates>139.5,35.7</coordinates></Point>
+</Placemark>"#,
+        );
+        let english = &records[0];
+        assert_eq!(english.cell_id.as_deref(), Some("12345"));
+        assert_eq!(english.lac.as_deref(), Some("678"));
+        assert_eq!(english.cgi.as_deref(), Some("440-10-678-12345"));
+        assert_eq!(english.operator_code.as_deref(), Some("44010"));
+        assert_eq!(english.network_mode.as_deref(), Some("FDD"));
+
+        let japanese = &records[1];
+        assert_eq!(japanese.cell_id.as_deref(), Some("54321"));
+        assert_eq!(japanese.network_mode.as_deref(), Some("TDD"));
+        assert_eq!(japanese.lac, None);
+    }
+
+    #[test]
+    fn reads_times_from_time_stamps_and_spans() {
+        let records = parse(
+            r#"<Placemark>
+  <TimeStamp><when>2025-10-03T10:20:09Z</when></TimeStamp>
+  <Point><coordinates>139.5,35.7</coordinates></Point>
+</Placemark>
+<Placemark>
+  <TimeSpan><begin>2025-10-03T19:20:10+09:00</begin><end>2025-10-03T19:20:11+09:00</end></TimeSpan>
+  <Point><coordinates>139.5,35.7</coordinates></Point>
+</Placemark>
+<Placemark>
+  <ExtendedData><Data name="時間"><value>2025.10.03_10.00.00</value></Data></ExtendedData>
+  <TimeStamp><when>2025-10-03T10:20:12Z</when></TimeStamp>
+  <Point><coordinates>139.5,35.7</coordinates></Point>
+</Placemark>"#,
+        );
+        let times: Vec<String> = records.iter().map(|r| r.timestamp.to_rfc3339()).collect();
+        // The explicit <when> wins over ExtendedData
+        assert_eq!(
+            times,
+            [
+                "2025-10-03T10:20:09+00:00",
+                "2025-10-03T10:20:10+00:00",
+                "2025-10-03T10:20:12+00:00",
+            ]
+        );
+    }
+}
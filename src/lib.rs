@@ -0,0 +1,38 @@
+//! Parsers for G-NetTrack Lite logs (CSV, KML, GPX) and an InfluxDB 1.x/2.x/3.x uploader.
+//!
+//! The `gnt2influx` binary is a thin CLI over this library. Records can also be parsed
+//! from another program:
+//!
+//! ```
+//! use gnt2influx::LogParser;
+//! use gnt2influx::parser::Delimiter;
+//!
+//! let csv = "Timestamp\tLongitude\tLatitude\tLevel\n2025-10-03 10:20:09\t139.5\t35.7\t-95\n";
+//! let records = LogParser::new(1000, true)
+//!     .parse_reader(csv.as_bytes(), Delimiter::Auto)
+//!     .unwrap();
+//!
+//! assert_eq!(records.len(), 1);
+//! assert_eq!(records[0].level, Some(-95.0));
+//! assert_eq!(records[0].longitude, Some(139.5));
+//! ```
+
+pub mod checkpoint;
+pub mod config;
+pub mod export;
+pub mod gps_track;
+pub mod gpx_parser;
+pub mod influx_client;
+pub mod input_format;
+pub mod kml_parser;
+pub mod parser;
+pub mod processing;
+pub mod redact;
+pub mod stats;
+#[cfg(test)]
+mod test_support;
+
+pub use config::Config;
+pub use influx_client::InfluxClient;
+pub use kml_parser::KmlParser;
+pub use parser::{GNetTrackRecord, LogParser};
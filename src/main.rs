@@ -1,159 +1,569 @@
-mod config;
-mod influx_client;
-mod kml_parser;
-mod parser;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, TimeDelta, Timelike, Utc};
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use log::{LevelFilter, debug, error, info, warn};
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use clap::{Arg, Command};
-use log::{LevelFilter, debug, error, info};
-use std::path::Path;
+mod cli;
 
-use crate::config::Config;
-use crate::influx_client::InfluxClient;
-use crate::kml_parser::KmlParser;
-use crate::parser::LogParser;
+use cli::Mode;
+use gnt2influx::checkpoint::Checkpoint;
+use gnt2influx::config::{Config, InfluxDbConfig};
+use gnt2influx::export::{self, OutputFormat};
+use gnt2influx::gps_track::GpsTrack;
+use gnt2influx::gpx_parser::GpxParser;
+use gnt2influx::influx_client::{FieldValue, InfluxClient, QueryTable};
+use gnt2influx::input_format::{
+    InputFormat, detect_format, download, is_gzip, is_url, open_input, sniff_format, url_path,
+};
+use gnt2influx::kml_parser::KmlParser;
+use gnt2influx::parser::{Delimiter, GNetTrackRecord, LogParser, ParseOutcome, parse_timezone};
+use gnt2influx::processing::{
+    BoundingBox, Deduper, FilenameTags, classify_signal, derive_bearing, derive_distance,
+    downsample, enforce_ranges, filter_by_bbox, filter_by_time, interpolate_gps, merge_sorted,
+    parse_time_shift, shift_timestamps, shift_to_now, split_operator_code,
+};
+use gnt2influx::redact::redact_url;
+use gnt2influx::stats::RecordStats;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let matches = Command::new("gnt2influx")
-        .version("0.1.0")
-        .author("Your Name")
-        .about("Converts G-NetTrack Lite log files to InfluxDB format and uploads them")
-        .arg(
-            Arg::new("input")
-                .short('i')
-                .long("input")
-                .value_name("FILE")
-                .help("Path to G-NetTrack log file")
-                .required_unless_present("test-connection"),
-        )
-        .arg(
-            Arg::new("config")
-                .short('c')
-                .long("config")
-                .value_name("FILE")
-                .help("Path to configuration file")
-                .default_value("config.toml"),
-        )
-        .arg(
-            Arg::new("test-connection")
-                .long("test-connection")
-                .help("Test InfluxDB connection without uploading data")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .arg(
-            Arg::new("dry-run")
-                .long("dry-run")
-                .help("Parse the log file but don't upload to InfluxDB")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .arg(
-            Arg::new("verbose")
-                .short('v')
-                .long("verbose")
-                .help("Enable verbose logging")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .get_matches();
+    let matches = cli::build().get_matches();
+    let (mode, args) = cli::mode(&matches)?;
 
-    // Initialize logging
-    let log_level = if matches.get_flag("verbose") {
-        LevelFilter::Debug
-    } else {
-        LevelFilter::Info
-    };
+    // Initialize logging. The logger accepts everything and the level is applied through
+    // `log::set_max_level`, so it can still be lowered once the config file is read.
+    let verbose = cli::flag(args, "verbose");
+    let quiet = cli::flag(args, "quiet");
+    let mut logger = env_logger::Builder::new();
+    logger.filter_level(LevelFilter::Trace);
+    if cli::value::<String>(args, "log-format").map(String::as_str) == Some("json") {
+        logger.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{line}")
+        });
+    }
+    logger.init();
+    log::set_max_level(resolve_log_level(verbose, quiet, None));
 
-    env_logger::Builder::new().filter_level(log_level).init();
+    // Generate a default configuration file if requested
+    if let Some(init_path) = cli::value::<String>(args, "init-config") {
+        let init_path = Path::new(init_path);
+        if init_path.exists() && !cli::flag(args, "force") {
+            return Err(anyhow!(
+                "{} already exists, use --force to overwrite it",
+                init_path.display()
+            ));
+        }
+        Config::default().save(init_path)?;
+        info!("Wrote default configuration to {}", init_path.display());
+        return Ok(());
+    }
 
     // Load configuration
-    let config_path = matches.get_one::<String>("config").unwrap();
-    let config = if Path::new(config_path).exists() {
+    let config_path = cli::value::<String>(args, "config").unwrap();
+    let mut config = if let Some(profile) = cli::value::<String>(args, "profile") {
+        let config_dir = Path::new(cli::value::<String>(args, "config-dir").unwrap());
+        let base_path = if args.value_source("config") == Some(ValueSource::DefaultValue) {
+            config_dir.join(config_path)
+        } else {
+            PathBuf::from(config_path)
+        };
+        let profile_path = config_dir.join(format!("config.{profile}.toml"));
+
+        let mut paths = Vec::new();
+        if base_path.exists() {
+            paths.push(base_path.as_path());
+        } else {
+            info!(
+                "Base configuration file {} not found, using defaults under profile '{profile}'",
+                base_path.display()
+            );
+        }
+        let config = Config::from_files_with_profile(&paths, profile, &profile_path)?;
+        info!("Using configuration profile '{profile}'");
+        config
+    } else if Path::new(config_path).exists() {
         Config::from_file(config_path)?
     } else {
         info!("Configuration file not found, using default settings");
         Config::default()
     };
-
-    // Override log level from config if not set via CLI
-    if !matches.get_flag("verbose") {
-        let level = match config.logging.level.to_lowercase().as_str() {
-            "error" => LevelFilter::Error,
-            "warn" => LevelFilter::Warn,
-            "info" => LevelFilter::Info,
-            "debug" => LevelFilter::Debug,
-            "trace" => LevelFilter::Trace,
-            _ => LevelFilter::Info,
-        };
-        env_logger::Builder::new()
-            .filter_level(level)
-            .try_init()
-            .ok();
+    config.apply_env_overrides();
+    if let Some(measurement_type) = cli::value::<String>(args, "measurement-type") {
+        for target in &mut config.influxdb {
+            target.measurement_type = measurement_type.clone();
+        }
     }
+    if let Some(operator) = cli::value::<String>(args, "operator") {
+        config.processing.kml_operator_name = Some(operator.clone());
+    }
+    config.validate()?;
 
-    // Create InfluxDB client
-    let influx_client = InfluxClient::new(&config.influxdb)?;
+    // Apply the log level from config unless --verbose or --quiet was given
+    log::set_max_level(resolve_log_level(
+        verbose,
+        quiet,
+        Some(&config.logging.level),
+    ));
 
+    // Create InfluxDB client
+    // The bar would be interleaved with per-batch debug logging, so it is only used at
+    // normal verbosity
+    let show_progress = !cli::flag(args, "no-progress") && !verbose && !quiet;
+    let constant_tags = cli::values(args, "tag")
+        .map(|pair| parse_key_value(pair, "--tag"))
+        .collect::<Result<Vec<_>>>()?;
+    let constant_fields = cli::values(args, "field")
+        .map(|pair| parse_constant_field(pair))
+        .collect::<Result<Vec<_>>>()?;
+    let filename_tags = config
+        .processing
+        .filename_tag_regex
+        .as_deref()
+        .map(FilenameTags::parse)
+        .transpose()?;
+    let shutdown = Arc::new(AtomicBool::new(false));
+    // Built only once they are needed, so --preview and `stats` work without a reachable
+    // InfluxDB
+    let build_targets = || {
+        config
+            .influxdb
+            .iter()
+            .map(|target| {
+                Ok(InfluxClient::new(target)?
+                    .with_column_map(&config.processing.column_map)
+                    .with_extra_tags(filename_tags.iter().flat_map(FilenameTags::names))
+                    .with_progress(show_progress)
+                    .with_fail_fast(cli::flag(args, "fail-fast"))
+                    .with_max_writes_per_sec(config.processing.max_writes_per_sec)
+                    .with_constants(constant_tags.clone(), constant_fields.clone())
+                    .with_retry(&config.retry)
+                    .with_shutdown(shutdown.clone()))
+            })
+            .collect::<Result<Vec<_>>>()
+    };
+    let labels: Vec<String> = config
+        .influxdb
+        .iter()
+        .map(|target| {
+            format!(
+                "database '{}' on {}",
+                target.database,
+                redact_url(&target.url)
+            )
+        })
+        .collect();
     // Test connection if requested
-    if matches.get_flag("test-connection") {
-        info!("Testing InfluxDB connection...");
-        influx_client.test_connection().await?;
-        info!("Connection test successful!");
+    if mode == Mode::Test {
+        if cli::flag(args, "test-connection") {
+            warn!("--test-connection is deprecated, use `gnt2influx test` instead");
+        }
+        let targets = build_targets()?;
+        if let [client] = targets.as_slice() {
+            info!("Testing InfluxDB connection...");
+            client.test_connection().await?;
+            info!("Connection test successful!");
+            return Ok(());
+        }
+
+        let mut failed = Vec::new();
+        for (client, label) in targets.iter().zip(&labels) {
+            info!("Testing connection to {label}...");
+            match client.test_connection().await {
+                Ok(()) => info!("Connection to {label} successful"),
+                Err(e) => {
+                    error!("Connection to {label} failed: {e}");
+                    failed.push(label.as_str());
+                }
+            }
+        }
+        if !failed.is_empty() {
+            return Err(anyhow!(
+                "Connection test failed for {} of {} targets: {}",
+                failed.len(),
+                targets.len(),
+                failed.join(", ")
+            ));
+        }
+        info!(
+            "Connection test successful for all {} targets!",
+            targets.len()
+        );
         return Ok(());
     }
 
-    // Get input file
-    let input_file = match matches.get_one::<String>("input") {
-        Some(file) => file,
-        None => {
-            error!("Input file is required when not testing connection");
+    // Get input files
+    let input_files: Vec<&String> = cli::values(args, "input").collect();
+    if input_files.is_empty() {
+        error!("Input file is required when not testing connection");
+        std::process::exit(1);
+    }
+    if matches.subcommand().is_none() && cli::flag(args, "dry-run") {
+        warn!("--dry-run without a subcommand is deprecated, use `gnt2influx upload --dry-run`");
+    }
+
+    let format_override = cli::value::<String>(args, "format")
+        .map(|format| InputFormat::parse(format))
+        .transpose()?;
+
+    let since = parse_time_bound(cli::value::<String>(args, "since"), "--since")?;
+    let until = parse_time_bound(cli::value::<String>(args, "until"), "--until")?;
+    let filter_time = since.is_some() || until.is_some();
+    let mut outside_time_range = 0;
+    let bbox = cli::value::<String>(args, "bbox")
+        .map(|bbox| BoundingBox::parse(bbox))
+        .transpose()?;
+    let mut outside_bbox = 0;
+
+    let mut input_paths = Vec::new();
+    for input_file in &input_files {
+        let path = Path::new(input_file.as_str());
+        if input_file.as_str() == STDIN_INPUT || is_url(input_file) {
+            input_paths.push(path.to_path_buf());
+            continue;
+        }
+        if !path.exists() {
+            error!("Input file does not exist: {input_file}");
             std::process::exit(1);
         }
-    };
+        input_paths.extend(collect_input_files(path)?);
+    }
 
-    if !Path::new(input_file).exists() {
-        error!("Input file does not exist: {input_file}");
-        std::process::exit(1);
+    let gps_track = cli::value::<String>(args, "gps-track")
+        .map(|path| load_gps_track(path, &config))
+        .transpose()?;
+    let gps_tolerance = cli::value::<f64>(args, "gps-tolerance")
+        .map(|secs| TimeDelta::milliseconds((secs * 1000.0) as i64))
+        .unwrap_or_default();
+    let gps_override = cli::flag(args, "gps-override");
+    let mut gps_matched = 0;
+
+    let parse_started = Instant::now();
+
+    // Parse every input, recording which slice of `records` came from which file so that
+    // uploads can either coalesce everything into full batches or batch each file on its own.
+    let downsample_bucket =
+        cli::value::<u64>(args, "downsample").map(|secs| Duration::from_secs(*secs));
+    let mut deduper = (cli::flag(args, "dedupe") || config.processing.dedupe).then(Deduper::new);
+    let mut duplicates = 0;
+    let mut checkpoint =
+        cli::value::<String>(args, "checkpoint").map(|path| Checkpoint::load(Path::new(path)));
+    let mut already_uploaded = 0;
+    let mut skipped = 0;
+    let mut records = Vec::new();
+    let mut file_ranges = Vec::new();
+    for input_path in &input_paths {
+        let input_file = input_path.to_string_lossy();
+        info!("Processing log file: {input_file}");
+        let outcome = parse_input(&input_file, format_override, &config).await?;
+        skipped += outcome.skipped;
+        let mut parsed = outcome.records;
+        info!("Parsed {} records from {input_file}", parsed.len());
+
+        if let Some(filename_tags) = &filename_tags {
+            parsed = filename_tags.apply(parsed, input_path);
+        }
+
+        if filter_time {
+            let before = parsed.len();
+            parsed = filter_by_time(parsed, since, until);
+            outside_time_range += before - parsed.len();
+        }
+
+        if let Some(last) = checkpoint
+            .as_ref()
+            .and_then(|checkpoint| checkpoint.last_uploaded(&input_file))
+        {
+            let before = parsed.len();
+            parsed.retain(|record| record.timestamp > last);
+            already_uploaded += before - parsed.len();
+        }
+
+        if config.processing.split_operator_code {
+            parsed = split_operator_code(parsed);
+        }
+
+        parsed = enforce_ranges(parsed, config.processing.out_of_range);
+
+        if let Some(track) = &gps_track {
+            gps_matched += track.apply(&mut parsed, gps_tolerance, gps_override);
+        }
+
+        if cli::flag(args, "interpolate-gps") {
+            parsed = interpolate_gps(parsed);
+        }
+
+        if let Some(bbox) = &bbox {
+            let before = parsed.len();
+            parsed = filter_by_bbox(parsed, bbox);
+            outside_bbox += before - parsed.len();
+        }
+
+        if let Some(deduper) = deduper.as_mut() {
+            let before = parsed.len();
+            parsed = deduper.dedupe(parsed);
+            duplicates += before - parsed.len();
+        }
+
+        if let Some(bucket) = downsample_bucket {
+            let before = parsed.len();
+            parsed = downsample(parsed, bucket);
+            debug!(
+                "Downsampled {before} records to {} from {input_file}",
+                parsed.len()
+            );
+        }
+
+        if cli::flag(args, "derive-distance") {
+            parsed = derive_distance(parsed);
+        }
+
+        if cli::flag(args, "derive-bearing") {
+            parsed = derive_bearing(parsed);
+        }
+
+        if cli::flag(args, "classify-signal") {
+            parsed = classify_signal(parsed);
+        }
+
+        let start = records.len();
+        records.extend(parsed);
+        file_ranges.push((input_file.to_string(), start..records.len()));
+    }
+
+    info!(
+        "Successfully parsed {} records from {} files",
+        records.len(),
+        input_paths.len()
+    );
+    if skipped > 0 && cli::flag(args, "strict-exit") {
+        return Err(anyhow!(
+            "{skipped} invalid records were skipped while parsing (--strict-exit)"
+        ));
+    }
+
+    // When batches are coalesced, several inputs (e.g. a CSV and its KML) are interleaved
+    // into one chronological stream. The merged records no longer map to per-file slices,
+    // so each file's checkpoint is later advanced up to the newest uploaded timestamp.
+    let mut merged_inputs = Vec::new();
+    if config.processing.coalesce_batches && file_ranges.len() > 1 {
+        let mut parts = Vec::with_capacity(file_ranges.len());
+        for (file, range) in file_ranges.drain(..).rev() {
+            let part = records.split_off(range.start);
+            if let Some(newest) = part.iter().map(|r| r.timestamp).max() {
+                merged_inputs.push((file, newest));
+            }
+            parts.push(part);
+        }
+        parts.reverse();
+        records = merge_sorted(parts);
+        debug!(
+            "Merged {} inputs into {} records in timestamp order",
+            input_paths.len(),
+            records.len()
+        );
+    }
+
+    if filter_time {
+        info!("Dropped {outside_time_range} records outside the requested time range");
     }
 
-    info!("Processing log file: {input_file}");
+    if checkpoint.is_some() {
+        info!("Skipped {already_uploaded} records already uploaded according to the checkpoint");
+    }
+
+    if gps_track.is_some() {
+        info!("Set the position of {gps_matched} records from the GPS track");
+    }
 
-    // Parse the log file - detect format by extension
-    let records = if input_file.to_lowercase().ends_with(".kml") {
-        let kml_parser = KmlParser::new(config.processing.skip_invalid);
-        kml_parser.parse_file(input_file)?
+    if bbox.is_some() {
+        info!("Dropped {outside_bbox} records outside the bounding box");
+    }
+
+    if deduper.is_some() {
+        info!("Dropped {duplicates} duplicate records");
+    }
+
+    let offset = cli::value::<usize>(args, "offset").copied();
+    let limit = cli::value::<usize>(args, "limit").copied();
+    if offset.is_some() || limit.is_some() {
+        let total = records.len();
+        (records, file_ranges) = slice_records(records, file_ranges, offset.unwrap_or(0), limit);
+        info!(
+            "Selected {} of {total} records with --offset/--limit",
+            records.len()
+        );
+    }
+
+    let time_shift = if cli::flag(args, "time-now-align") {
+        Some(shift_to_now(&records, Utc::now()))
     } else {
-        let parser = LogParser::new(config.processing.batch_size, config.processing.skip_invalid);
-        parser.parse_file(input_file)?
+        cli::value::<String>(args, "time-shift")
+            .map(|value| parse_time_shift(value))
+            .transpose()?
     };
+    if let Some(shift) = time_shift {
+        records = shift_timestamps(records, shift);
+        info!("Shifted all timestamps by {}s", shift.num_seconds());
+    }
+
+    if let Some(rows) = cli::value::<usize>(args, "preview") {
+        print!(
+            "{}",
+            QueryTable::preview(&records[..records.len().min(*rows)])
+        );
+        return Ok(());
+    }
 
-    info!("Successfully parsed {} records", records.len());
+    if mode == Mode::Stats {
+        println!("{}", RecordStats::from_records(&records));
+        return Ok(());
+    }
+
+    if cli::flag(args, "stats") {
+        info!(
+            "Record statistics:\n{}",
+            RecordStats::from_records(&records)
+        );
+    }
 
     if records.is_empty() {
         info!("No records to process");
         return Ok(());
     }
 
+    let targets = build_targets()?;
+    // Output files and dry runs are formatted for the first target
+    let influx_client = &targets[0];
+
+    // Records of one series sharing a timestamp overwrite each other in InfluxDB
+    if cli::flag(args, "bump-duplicate-timestamps") {
+        let bumped = influx_client.bump_duplicate_timestamps(&mut records);
+        if bumped > 0 {
+            info!("Moved {bumped} records with duplicate timestamps forward to keep them");
+        }
+    } else {
+        let duplicates = influx_client.count_duplicate_timestamps(&records);
+        if duplicates > 0 {
+            warn!(
+                "{duplicates} records share their timestamp and tags with an earlier record and would overwrite it; use --bump-duplicate-timestamps to keep them"
+            );
+        }
+    }
+
+    // A tag holding e.g. a unique value per row would create a series per point
+    let max_cardinality = cli::value::<usize>(args, "max-cardinality")
+        .copied()
+        .or(config.processing.max_series_cardinality);
+    if let Some(limit) = max_cardinality {
+        let cardinality = influx_client.estimate_cardinality(&records);
+        if cardinality.series > limit {
+            let worst = cardinality
+                .worst_tag
+                .map(|(tag, values)| {
+                    format!(
+                        "; tag '{tag}' has {values} distinct values, consider listing it in fields"
+                    )
+                })
+                .unwrap_or_default();
+            return Err(anyhow!(
+                "The records would create {} series, more than the limit of {limit}{worst}",
+                cardinality.series
+            ));
+        }
+        info!("The records form {} series", cardinality.series);
+    }
+
     // Debug: print first few records to understand the data structure
-    if matches.get_flag("verbose") {
+    if cli::flag(args, "verbose") {
         for (i, record) in records.iter().take(3).enumerate() {
             debug!("Record {}: {:?}", i + 1, record);
         }
     }
 
+    // Export to a file instead of uploading. `upload` has no --output-format; its --output
+    // is the dry-run preview below.
+    if let Some(output_path) = cli::value::<String>(args, "output")
+        && let Some(format) = cli::value::<String>(args, "output-format")
+    {
+        let format = OutputFormat::parse(format)?;
+        let written = match format {
+            OutputFormat::Influx => {
+                let lines = influx_client.format_records_for_influx(&records)?;
+                export::write_line_protocol(output_path, &lines)?;
+                records.len()
+            }
+            OutputFormat::Json => {
+                export::write_json(output_path, &records)?;
+                records.len()
+            }
+            OutputFormat::Ndjson => {
+                export::write_ndjson(output_path, &records)?;
+                records.len()
+            }
+            OutputFormat::Parquet => {
+                export::write_parquet(output_path, &records)?;
+                records.len()
+            }
+            OutputFormat::Geojson => {
+                let features = export::write_geojson(output_path, &records)?;
+                if features < records.len() {
+                    info!(
+                        "Skipped {} records without coordinates",
+                        records.len() - features
+                    );
+                }
+                features
+            }
+        };
+        info!("Wrote {written} records as {format:?} to {output_path}");
+        return Ok(());
+    }
+
+    // Write to SQLite instead of uploading
+    if let Some(sqlite_path) = cli::value::<String>(args, "sqlite") {
+        export::write_sqlite(sqlite_path, &records)?;
+        info!(
+            "Inserted {} records into the measurements table of {sqlite_path}",
+            records.len()
+        );
+        return Ok(());
+    }
+
     // Dry run - just parse and exit
-    if matches.get_flag("dry-run") {
+    if cli::flag(args, "dry-run") {
         info!(
             "Dry run completed. {} records would be uploaded.",
             records.len()
         );
 
+        if let Some(output_path) = cli::value::<String>(args, "output") {
+            let lines = influx_client.planned_lines(&records)?;
+            export::write_line_protocol(output_path, &lines)?;
+            info!(
+                "Wrote the line protocol for {} records to {output_path}",
+                lines.len()
+            );
+        }
+
         // Show what InfluxDB queries would look like for first few records
-        if matches.get_flag("verbose") {
+        if cli::flag(args, "verbose") {
             info!("Sample InfluxDB line protocol format (dry run):");
-            let influx_client = InfluxClient::new(&config.influxdb)?;
             // Take first 3 records for debugging
             let sample_records: Vec<_> = records.iter().take(3).cloned().collect();
-            match influx_client.format_records_for_influx(&sample_records) {
+            match influx_client.planned_lines(&sample_records) {
                 Ok(formatted_lines) => {
                     for (i, line) in formatted_lines.iter().enumerate() {
                         info!("InfluxDB line {}: {}", i + 1, line);
@@ -166,49 +576,720 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
-    // Test connection and create database
+    // Upload records to every target. A failing target doesn't stop the others.
+    let parse_time = parse_started.elapsed();
+    let upload_started = Instant::now();
+    watch_ctrl_c(shutdown.clone());
+    let units: Vec<Range<usize>> = if config.processing.coalesce_batches {
+        std::iter::once(0..records.len()).collect()
+    } else {
+        file_ranges.iter().map(|(_, range)| range.clone()).collect()
+    };
+    let fan_out = targets.len() > 1;
+    let mut uploaded = vec![usize::MAX; units.len()];
+    let mut attempted = 0;
+    let mut written = 0;
+    let mut batches = 0;
+    let mut interrupted = false;
+    let mut succeeded = Vec::new();
+    let mut failed = Vec::new();
+    for ((client, target), label) in targets.iter().zip(&config.influxdb).zip(&labels) {
+        if shutdown.load(Ordering::SeqCst) {
+            interrupted = true;
+            break;
+        }
+        if fan_out {
+            info!("Uploading {} records to {label}...", records.len());
+        }
+        let (progress, result) = upload_to(
+            client,
+            target,
+            &records,
+            &units,
+            &file_ranges,
+            &config,
+            args,
+        )
+        .await;
+        attempted += 1;
+        for (total, count) in uploaded.iter_mut().zip(&progress.uploaded) {
+            *total = (*total).min(*count);
+        }
+        written = progress.uploaded.iter().sum();
+        batches += progress.batches;
+        interrupted |= progress.interrupted;
+        match result {
+            Ok(()) if !progress.interrupted => succeeded.push((client, label)),
+            Ok(()) => {}
+            Err(e) => {
+                error!("Failed to upload records to {label}: {e}");
+                failed.push((label, e));
+            }
+        }
+    }
+    let upload_time = upload_started.elapsed();
+    // Targets skipped after an interrupt received nothing
+    if interrupted && attempted < targets.len() {
+        uploaded.iter_mut().for_each(|count| *count = 0);
+    }
+
+    // The checkpoint only advances past records that every target received
+    if let Some(checkpoint) = checkpoint.as_mut() {
+        if config.processing.coalesce_batches {
+            let everywhere = uploaded.first().copied().unwrap_or(0);
+            for (file, range) in &file_ranges {
+                let count = everywhere.saturating_sub(range.start);
+                checkpoint.record(file, &records[range.clone()], count);
+            }
+            if let Some(uploaded) = Checkpoint::uploaded_through(&records, everywhere) {
+                for (file, newest) in &merged_inputs {
+                    checkpoint.advance(file, (*newest).min(uploaded));
+                }
+            }
+        } else {
+            for ((file, range), count) in file_ranges.iter().zip(&uploaded) {
+                checkpoint.record(file, &records[range.clone()], *count);
+            }
+        }
+        checkpoint.save()?;
+    }
+
+    if interrupted {
+        warn!(
+            "Interrupted: uploaded {written} of {} records in {batches} batches",
+            records.len()
+        );
+        if checkpoint.is_some() {
+            info!("Checkpoint saved; re-run to upload the remaining records");
+        }
+        return Err(anyhow!("Upload interrupted"));
+    }
+
+    if fan_out {
+        info!(
+            "Uploaded to {} of {} targets",
+            succeeded.len(),
+            targets.len()
+        );
+        for (_, label) in &succeeded {
+            info!("  ok      {label}");
+        }
+        for (label, e) in &failed {
+            info!("  failed  {label}: {e}");
+        }
+    } else if let Some((_, label)) = succeeded.first() {
+        info!(
+            "Successfully uploaded {} records to InfluxDB in {batches} batches!",
+            records.len()
+        );
+        info!("Data is now available in {label}");
+        for measurement in influx_client.measurements(&records) {
+            info!("You can query the data with: SELECT * FROM \"{measurement}\" LIMIT 10");
+        }
+    }
+
+    if cli::flag(args, "verify") {
+        for (client, label) in &succeeded {
+            if fan_out {
+                info!("Verifying {label}...");
+            }
+            verify_upload(client, &records).await?;
+        }
+    }
+
+    if let Some(limit) = cli::value::<usize>(args, "show-recent") {
+        let range = cli::value::<String>(args, "recent-range").map(String::as_str);
+        for (client, label) in &succeeded {
+            for measurement in client.measurements(&records) {
+                match client.recent_points(&measurement, *limit, range).await {
+                    Ok(table) => {
+                        info!("Newest points of {measurement} in {label}:");
+                        print!("{table}");
+                    }
+                    Err(e) => warn!("Could not query the newest points in {label}: {e}"),
+                }
+            }
+        }
+    }
+
+    if !fan_out && let Some((_, e)) = failed.pop() {
+        return Err(e);
+    }
+    if !failed.is_empty() {
+        return Err(anyhow!(
+            "Upload failed for {} of {} targets: {}",
+            failed.len(),
+            targets.len(),
+            failed
+                .iter()
+                .map(|(label, _)| label.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    info!(
+        "Throughput: {} records in {:.2?} ({}); parsing took {parse_time:.2?}, uploading {upload_time:.2?} ({})",
+        records.len(),
+        parse_time + upload_time,
+        format_rate(records.len(), parse_time + upload_time),
+        format_rate(records.len(), upload_time)
+    );
+    info!("Successfully completed processing!");
+    Ok(())
+}
+
+/// Formats `count / elapsed` as records per second, or "n/a" when no time has elapsed.
+fn format_rate(count: usize, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        format!("{:.1} records/s", count as f64 / secs)
+    } else {
+        "n/a records/s".to_string()
+    }
+}
+
+/// How far an upload to one target got.
+struct UploadProgress {
+    /// Leading records written of each upload unit
+    uploaded: Vec<usize>,
+    batches: usize,
+    interrupted: bool,
+}
+
+/// Tests the connection to one target, creates its database and uploads `records` to it in
+/// `units`: either all records at once or one range per input file. The progress is
+/// returned even when the upload fails.
+async fn upload_to(
+    client: &InfluxClient,
+    target: &InfluxDbConfig,
+    records: &[GNetTrackRecord],
+    units: &[Range<usize>],
+    file_ranges: &[(String, Range<usize>)],
+    config: &Config,
+    args: &ArgMatches,
+) -> (UploadProgress, Result<()>) {
+    let mut progress = UploadProgress {
+        uploaded: vec![0; units.len()],
+        batches: 0,
+        interrupted: false,
+    };
+
     info!("Testing InfluxDB connection...");
-    match influx_client.test_connection().await {
+    match client.test_connection().await {
         Ok(_) => info!("InfluxDB connection successful"),
         Err(e) => {
             error!("InfluxDB connection failed: {e}");
             info!(
                 "Please ensure InfluxDB is running on {}",
-                config.influxdb.url
+                redact_url(&target.url)
             );
             info!(
                 "You can start InfluxDB 2.x with Docker: docker run -d --name influxdb2 -p 8086:8086 -e DOCKER_INFLUXDB_INIT_MODE=setup -e DOCKER_INFLUXDB_INIT_USERNAME=admin -e DOCKER_INFLUXDB_INIT_PASSWORD=password123 -e DOCKER_INFLUXDB_INIT_ORG=my-org -e DOCKER_INFLUXDB_INIT_BUCKET=gnettrack -e DOCKER_INFLUXDB_INIT_ADMIN_TOKEN=my-super-secret-auth-token influxdb:2.7"
             );
-            return Err(e);
+            return (progress, Err(e));
         }
     }
 
-    info!("Creating database if it doesn't exist...");
-    influx_client.create_database_if_not_exists().await?;
+    if target.create_database && !cli::flag(args, "no-create-db") {
+        info!("Creating database if it doesn't exist...");
+        if let Err(e) = client.create_database_if_not_exists().await {
+            return (progress, Err(e));
+        }
+    } else {
+        debug!("Skipping database creation");
+    }
 
-    // Upload records to InfluxDB
     info!("Uploading {} records to InfluxDB...", records.len());
-    match influx_client
-        .write_records_batch(&records, config.processing.batch_size)
-        .await
-    {
-        Ok(_) => {
-            info!(
-                "Successfully uploaded {} records to InfluxDB!",
-                records.len()
-            );
-            info!(
-                "Data is now available in database '{}' on {}",
-                config.influxdb.database, config.influxdb.url
-            );
-            info!("You can query the data with: SELECT * FROM network_measurements LIMIT 10");
+    let mut failed_files = Vec::new();
+    for (i, unit) in units.iter().enumerate() {
+        if client.shutting_down() {
+            progress.interrupted = true;
+            break;
         }
-        Err(e) => {
-            error!("Failed to upload records to InfluxDB: {e}");
-            return Err(e);
+        // Without coalescing, each unit is one file's range
+        let file = (!config.processing.coalesce_batches).then(|| file_ranges[i].0.as_str());
+        if let Some(file) = file {
+            debug!("Uploading {} records from {file}", unit.len());
+        }
+        match client
+            .write_records_batch(
+                &records[unit.clone()],
+                config.processing.batch_size,
+                config.processing.concurrency,
+            )
+            .await
+        {
+            Ok(summary) => {
+                progress.uploaded[i] = summary.records;
+                progress.batches += summary.batches;
+                if summary.interrupted {
+                    progress.interrupted = true;
+                    break;
+                }
+            }
+            Err(e) => match file {
+                Some(file) if !cli::flag(args, "fail-fast") => {
+                    error!("Failed to upload {file}: {e}");
+                    failed_files.push(file);
+                }
+                _ => return (progress, Err(e)),
+            },
         }
     }
 
-    info!("Successfully completed processing!");
+    if !failed_files.is_empty() {
+        let error = anyhow!(
+            "Upload failed for {} of {} files: {}",
+            failed_files.len(),
+            file_ranges.len(),
+            failed_files.join(", ")
+        );
+        return (progress, Err(error));
+    }
+    (progress, Ok(()))
+}
+
+/// Sets `shutdown` on the first Ctrl-C so uploads stop after their in-flight batches, and
+/// exits immediately on the second.
+fn watch_ctrl_c(shutdown: Arc<AtomicBool>) {
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        warn!("Interrupted; finishing in-flight batches. Press Ctrl-C again to abort");
+        shutdown.store(true, Ordering::SeqCst);
+        if tokio::signal::ctrl_c().await.is_ok() {
+            error!("Aborted");
+            std::process::exit(130);
+        }
+    });
+}
+
+/// Picks the log level: `--quiet` and `--verbose` win over the config file's level, which
+/// wins over the `info` default. Unknown config levels fall back to `info`.
+fn resolve_log_level(verbose: bool, quiet: bool, config_level: Option<&str>) -> LevelFilter {
+    if quiet {
+        return LevelFilter::Error;
+    }
+    if verbose {
+        return LevelFilter::Debug;
+    }
+    match config_level.map(str::to_lowercase).as_deref() {
+        Some("error") => LevelFilter::Error,
+        Some("warn") => LevelFilter::Warn,
+        Some("debug") => LevelFilter::Debug,
+        Some("trace") => LevelFilter::Trace,
+        _ => LevelFilter::Info,
+    }
+}
+
+/// Counts the points in the uploaded time range and warns if it differs from the number of
+/// records sent. Points written to the range by earlier uploads also make it differ.
+async fn verify_upload(influx_client: &InfluxClient, records: &[GNetTrackRecord]) -> Result<()> {
+    let (Some(first), Some(last)) = (
+        records.iter().map(|r| r.timestamp).min(),
+        records.iter().map(|r| r.timestamp).max(),
+    ) else {
+        return Ok(());
+    };
+    // Timestamps may be truncated to the write precision, so start at the whole second
+    let start = first.with_nanosecond(0).unwrap_or(first);
+
+    info!("Verifying upload...");
+    let mut count = 0;
+    for measurement in influx_client.measurements(records) {
+        count += influx_client
+            .count_records_in_range(&measurement, start, last)
+            .await?;
+    }
+    if count != records.len() as u64 {
+        warn!(
+            "Verification mismatch: sent {} records but found {count} points between {} and {}",
+            records.len(),
+            start.to_rfc3339(),
+            last.to_rfc3339()
+        );
+    } else {
+        info!(
+            "Verified {count} points between {} and {} ({} sent)",
+            start.to_rfc3339(),
+            last.to_rfc3339(),
+            records.len()
+        );
+    }
     Ok(())
 }
+
+/// Keeps at most `limit` records starting at `offset`, shrinking the per-file ranges to match.
+fn slice_records(
+    mut records: Vec<GNetTrackRecord>,
+    file_ranges: Vec<(String, std::ops::Range<usize>)>,
+    offset: usize,
+    limit: Option<usize>,
+) -> (Vec<GNetTrackRecord>, Vec<(String, std::ops::Range<usize>)>) {
+    let start = offset.min(records.len());
+    let end = limit.map_or(records.len(), |limit| {
+        start.saturating_add(limit).min(records.len())
+    });
+    records.truncate(end);
+    records.drain(..start);
+
+    let file_ranges = file_ranges
+        .into_iter()
+        .filter_map(|(file, range)| {
+            let from = range.start.clamp(start, end) - start;
+            let to = range.end.clamp(start, end) - start;
+            (from < to).then_some((file, from..to))
+        })
+        .collect();
+    (records, file_ranges)
+}
+
+/// Splits a `KEY=VALUE` argument of `flag`.
+fn parse_key_value(pair: &str, flag: &str) -> Result<(String, String)> {
+    match pair.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(anyhow!("{flag} expects KEY=VALUE, got: {pair}")),
+    }
+}
+
+/// Parses a `--field KEY=VALUE`, typing the value as a float when it is a number.
+fn parse_constant_field(pair: &str) -> Result<(String, FieldValue)> {
+    let (key, value) = parse_key_value(pair, "--field")?;
+    let value = match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => FieldValue::Float(number),
+        _ => FieldValue::Text(value),
+    };
+    Ok((key, value))
+}
+
+/// Parses an optional RFC3339 `--since`/`--until` value.
+fn parse_time_bound(value: Option<&String>, flag: &str) -> Result<Option<DateTime<Utc>>> {
+    value
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|dt| dt.with_timezone(&Utc))
+                .map_err(|e| anyhow!("Invalid {flag} timestamp '{value}': {e}"))
+        })
+        .transpose()
+}
+
+/// Input path that reads CSV log data from stdin.
+const STDIN_INPUT: &str = "-";
+
+const SUPPORTED_EXTENSIONS: &[&str] = &["csv", "txt", "kml", "gpx"];
+
+/// Returns `path` itself for a file, or every supported file below it (sorted) for a directory.
+fn collect_input_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry_path = entry?.path();
+            if entry_path.is_dir() {
+                dirs.push(entry_path);
+            } else if is_supported_input(&entry_path) {
+                files.push(entry_path);
+            } else {
+                debug!("Skipping unsupported file: {}", entry_path.display());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+fn is_supported_input(path: &Path) -> bool {
+    let path = if is_gzip(path) {
+        match path.file_stem() {
+            Some(stem) => Path::new(stem),
+            None => return false,
+        }
+    } else {
+        path
+    };
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Parses a single input file. The parser is chosen from `format` when given, then from a
+/// recognised extension, and finally by sniffing the file contents.
+async fn parse_input(
+    input_file: &str,
+    format: Option<InputFormat>,
+    config: &Config,
+) -> Result<ParseOutcome> {
+    if input_file == STDIN_INPUT {
+        if format.is_some_and(|format| format != InputFormat::Csv) {
+            return Err(anyhow!("Only CSV input can be read from stdin"));
+        }
+        return parse_reader(std::io::stdin().lock(), InputFormat::Csv, config);
+    }
+
+    if is_url(input_file) {
+        let body = download(input_file, config.primary_target()).await?;
+        let format = match format.or_else(|| {
+            url_path(input_file).and_then(|path| InputFormat::from_extension(Path::new(&path)))
+        }) {
+            Some(format) => format,
+            None => {
+                let format = sniff_format(&body);
+                debug!("Detected {format:?} content in {input_file}");
+                format
+            }
+        };
+        return parse_reader(body.as_slice(), format, config);
+    }
+
+    let path = Path::new(input_file);
+    let format = match format.or_else(|| InputFormat::from_extension(path)) {
+        Some(format) => format,
+        None => {
+            let format = detect_format(path)?;
+            debug!("Detected {format:?} content in {input_file}");
+            format
+        }
+    };
+    parse_reader(open_input(path)?, format, config)
+}
+
+/// Loads the `--gps-track` file: an NMEA log, or a GPX, KML or CSV file whose positioned
+/// records are used as fixes.
+fn load_gps_track(path: &str, config: &Config) -> Result<GpsTrack> {
+    let mut body = Vec::new();
+    open_input(Path::new(path))
+        .and_then(|mut reader| Ok(reader.read_to_end(&mut body)?))
+        .map_err(|e| anyhow!("Failed to read GPS track {path}: {e}"))?;
+    let track = if body.trim_ascii_start().starts_with(b"$") {
+        GpsTrack::parse_nmea(body.as_slice())?
+    } else {
+        let format =
+            InputFormat::from_extension(Path::new(path)).unwrap_or_else(|| sniff_format(&body));
+        GpsTrack::from_records(&parse_reader(body.as_slice(), format, config)?.records)
+    };
+    if track.is_empty() {
+        return Err(anyhow!("GPS track {path} contains no valid fixes"));
+    }
+    info!("Loaded {} GPS fixes from {path}", track.len());
+    Ok(track)
+}
+
+/// Parses `reader` as `format` with the parser settings from `config`.
+fn parse_reader(reader: impl Read, format: InputFormat, config: &Config) -> Result<ParseOutcome> {
+    let timezone = parse_timezone(&config.processing.input_timezone)?;
+    match format {
+        InputFormat::Kml => KmlParser::new(config.processing.skip_invalid)
+            .with_timezone(timezone)
+            .with_operator_name(config.processing.kml_operator_name.clone())
+            .parse_reader_outcome(reader),
+        InputFormat::Gpx => {
+            GpxParser::new(config.processing.skip_invalid).parse_reader_outcome(reader)
+        }
+        InputFormat::Csv => {
+            let comment_char = match config.processing.comment_char.as_bytes() {
+                [] => None,
+                [c] => Some(*c),
+                _ => {
+                    return Err(anyhow!(
+                        "comment_char must be a single ASCII character, got: {}",
+                        config.processing.comment_char
+                    ));
+                }
+            };
+            LogParser::new(config.processing.batch_size, config.processing.skip_invalid)
+                .with_timezone(timezone)
+                .with_columns(config.processing.columns.clone())
+                .with_column_map(&config.processing.column_map)
+                .with_keep_unknown_columns(config.processing.keep_unknown_columns)
+                .with_header_priority(&config.processing.header_priority)
+                .with_speed_unit(config.processing.speed_unit)
+                .with_null_tokens(&config.processing.null_tokens)
+                .with_decimal_comma(config.processing.decimal_comma)
+                .with_comment_char(comment_char)
+                .with_parallel(config.processing.parallel)
+                .parse_reader_outcome(reader, Delimiter::Auto)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn collects_supported_files_recursively() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("day1/morning")).unwrap();
+        for file in [
+            "b.csv",
+            "notes.md",
+            "day1/a.KML",
+            "day1/morning/c.gpx",
+            "day1/morning/d.csv.gz",
+            "day1/morning/e.json",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let files = collect_input_files(root).unwrap();
+        let relative: Vec<_> = files
+            .iter()
+            .map(|file| file.strip_prefix(root).unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            relative,
+            [
+                "b.csv",
+                "day1/a.KML",
+                "day1/morning/c.gpx",
+                "day1/morning/d.csv.gz"
+            ]
+        );
+
+        let file = root.join("notes.md");
+        assert_eq!(collect_input_files(&file).unwrap(), [file]);
+    }
+
+    #[tokio::test]
+    async fn stdin_only_accepts_csv() {
+        let config = Config::default();
+        let error = parse_input(STDIN_INPUT, Some(InputFormat::Kml), &config)
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Only CSV input can be read from stdin");
+    }
+
+    #[test]
+    fn parses_constant_tags_and_fields() {
+        assert_eq!(
+            parse_key_value("driver=alice=b", "--tag").unwrap(),
+            ("driver".to_string(), "alice=b".to_string())
+        );
+        assert!(parse_key_value("=alice", "--tag").is_err());
+        assert!(parse_key_value("driver", "--tag").is_err());
+
+        assert_eq!(
+            parse_constant_field("run=7").unwrap(),
+            ("run".to_string(), FieldValue::Float(7.0))
+        );
+        assert_eq!(
+            parse_constant_field("device=pixel7").unwrap(),
+            ("device".to_string(), FieldValue::Text("pixel7".to_string()))
+        );
+        assert_eq!(
+            parse_constant_field("gain=NaN").unwrap().1,
+            FieldValue::Text("NaN".to_string())
+        );
+    }
+
+    fn records(count: u32) -> Vec<GNetTrackRecord> {
+        (0..count)
+            .map(|s| {
+                serde_json::from_str(&format!(r#"{{"timestamp": "2025-10-03T10:20:{s:02}Z"}}"#))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn slices_records_and_file_ranges() {
+        let ranges = vec![("a.csv".to_string(), 0..3), ("b.csv".to_string(), 3..5)];
+
+        let (sliced, sliced_ranges) = slice_records(records(5), ranges.clone(), 2, Some(2));
+        assert_eq!(
+            sliced
+                .iter()
+                .map(|r| r.timestamp.timestamp() % 60)
+                .collect::<Vec<_>>(),
+            [2, 3]
+        );
+        assert_eq!(
+            sliced_ranges,
+            [("a.csv".to_string(), 0..1), ("b.csv".to_string(), 1..2)]
+        );
+
+        // An offset past the end leaves nothing
+        let (sliced, sliced_ranges) = slice_records(records(5), ranges.clone(), 9, None);
+        assert!(sliced.is_empty());
+        assert!(sliced_ranges.is_empty());
+
+        // A limit beyond what is left keeps the rest
+        let (sliced, sliced_ranges) = slice_records(records(5), ranges, 3, Some(100));
+        assert_eq!(sliced.len(), 2);
+        assert_eq!(sliced_ranges, [("b.csv".to_string(), 0..2)]);
+    }
+
+    #[test]
+    fn resolves_the_log_level() {
+        assert_eq!(resolve_log_level(false, false, None), LevelFilter::Info);
+        assert_eq!(
+            resolve_log_level(false, false, Some("WARN")),
+            LevelFilter::Warn
+        );
+        assert_eq!(
+            resolve_log_level(false, false, Some("loud")),
+            LevelFilter::Info
+        );
+        assert_eq!(
+            resolve_log_level(true, false, Some("error")),
+            LevelFilter::Debug
+        );
+        assert_eq!(
+            resolve_log_level(false, true, Some("trace")),
+            LevelFilter::Error
+        );
+
+        let both = cli::build().try_get_matches_from(["gnt2influx", "--quiet", "--verbose"]);
+        assert_eq!(
+            both.unwrap_err().kind(),
+            clap::error::ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn formats_the_rate_even_without_elapsed_time() {
+        assert_eq!(
+            format_rate(150, Duration::from_millis(1500)),
+            "100.0 records/s"
+        );
+        assert_eq!(format_rate(150, Duration::ZERO), "n/a records/s");
+        assert_eq!(format_rate(0, Duration::from_secs(2)), "0.0 records/s");
+    }
+
+    #[test]
+    fn parse_reader_reports_the_skipped_records() {
+        let mut config = Config::default();
+        config.processing.skip_invalid = true;
+
+        let csv = "Timestamp\tLevel\n\
+                   2025-10-03 10:20:09\t-95\n\
+                   yesterday\t-96\n\
+                   2025-10-03 10:20:11\t-97\n";
+        let outcome = parse_reader(csv.as_bytes(), InputFormat::Csv, &config).unwrap();
+        assert_eq!((outcome.records.len(), outcome.skipped), (2, 1));
+
+        let kml = r#"<kml><Document>
+<Placemark>
+  <ExtendedData><Data name="時間"><value>2025.10.03_10.20.09</value></Data></ExtendedData>
+  <Point><coordinates>139.5,35.7</coordinates></Point>
+</Placemark>
+<Placemark>
+  <ExtendedData><Data name="時間"><value>yesterday</value></Data></ExtendedData>
+  <Point><coordinates>139.5,35.7</coordinates></Point>
+</Placemark>
+</Document></kml>"#;
+        let outcome = parse_reader(kml.as_bytes(), InputFormat::Kml, &config).unwrap();
+        assert_eq!((outcome.records.len(), outcome.skipped), (1, 1));
+    }
+}
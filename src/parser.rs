@@ -1,19 +1,29 @@
+use crate::config::{ColumnMapping, ColumnType, DEFAULT_BATCH_SIZE, SpeedUnit};
+use crate::influx_client::FieldValue;
+use crate::input_format::open_input;
 use anyhow::{Result, anyhow};
-use chrono::{DateTime, NaiveDateTime, Utc};
-use csv::ReaderBuilder;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use csv::{ReaderBuilder, StringRecord};
 use log::{debug, warn};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Cursor, Read};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GNetTrackRecord {
     pub timestamp: DateTime<Utc>,
     pub longitude: Option<f64>,
     pub latitude: Option<f64>,
+    pub altitude: Option<f64>,
     pub speed: Option<f64>,
     pub operator_name: Option<String>,
     pub operator_code: Option<String>,
+    /// Set from `operator_code` by `processing::split_operator_code`
+    #[serde(default)]
+    pub mcc: Option<String>,
+    #[serde(default)]
+    pub mnc: Option<String>,
     pub cgi: Option<String>,
     pub cellname: Option<String>,
     pub node: Option<String>,
@@ -28,16 +38,45 @@ pub struct GNetTrackRecord {
     pub arfcn: Option<String>,
     pub dl_bitrate: Option<f64>,
     pub ul_bitrate: Option<f64>,
+    pub ping_avg: Option<f64>,
+    pub ping_min: Option<f64>,
+    pub ping_max: Option<f64>,
+    pub ping_loss: Option<f64>,
+    /// Distance in meters from the previous fix, set by `processing::derive_distance`
+    #[serde(default)]
+    pub distance_m: Option<f64>,
+    /// Heading in degrees from the previous fix, set by `processing::derive_bearing`
+    #[serde(default)]
+    pub bearing_deg: Option<f64>,
+    /// Signal quality bucket of `level`, set by `processing::classify_signal`
+    #[serde(default)]
+    pub signal_quality: Option<String>,
+    /// Whether the coordinates were filled in by `processing::interpolate_gps`
+    #[serde(default)]
+    pub gps_interpolated: bool,
+    /// Values from columns mapped through `processing.column_map`, and unknown columns with
+    /// `processing.keep_unknown_columns`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extra: HashMap<String, FieldValue>,
 }
 
 impl GNetTrackRecord {
+    /// Builds a record from a CSV row. Naive timestamps are interpreted in `timezone`, and
+    /// unknown columns are kept if `column_map` (keyed by lowercase header) names them, or
+    /// as string fields under their sanitized header with `keep_unknown`. Numeric cells are
+    /// read according to `numbers`.
     pub fn from_csv_record(
         record: &csv::StringRecord,
         headers: &csv::StringRecord,
+        timezone: &FixedOffset,
+        column_map: &HashMap<String, ColumnMapping>,
+        numbers: &NumberFormat,
+        keep_unknown: bool,
     ) -> Result<Self> {
         let mut timestamp = Utc::now();
         let mut longitude = None;
         let mut latitude = None;
+        let mut altitude = None;
         let mut speed = None;
         let mut operator_name = None;
         let mut operator_code = None;
@@ -55,75 +94,108 @@ impl GNetTrackRecord {
         let mut arfcn = None;
         let mut dl_bitrate = None;
         let mut ul_bitrate = None;
+        let mut ping_avg = None;
+        let mut ping_min = None;
+        let mut ping_max = None;
+        let mut ping_loss = None;
+        let mut extra = HashMap::new();
 
         for (i, value) in record.iter().enumerate() {
-            if let Some(header) = headers.get(i) {
+            // Blank headers mark columns dropped by `resolve_header_conflicts`
+            if let Some(header) = headers.get(i).filter(|header| !header.is_empty()) {
                 let header_lower = header.to_lowercase();
 
-                match header_lower.as_str() {
-                    "timestamp" | "time" => {
-                        timestamp = parse_timestamp(value)?;
+                match target_field(&header_lower) {
+                    Some("timestamp") => {
+                        timestamp = parse_timestamp(value, header, timezone)?;
                     }
-                    "longitude" | "lon" => {
-                        longitude = parse_float_optional(value);
+                    Some("longitude") => {
+                        longitude = parse_float_optional(value, numbers);
                     }
-                    "latitude" | "lat" => {
-                        latitude = parse_float_optional(value);
+                    Some("latitude") => {
+                        latitude = parse_float_optional(value, numbers);
                     }
-                    "speed" => {
-                        speed = parse_float_optional(value);
+                    Some("altitude") => {
+                        altitude = parse_float_optional(value, numbers);
                     }
-                    "operator" | "operator_name" => {
+                    Some("speed") => {
+                        speed = parse_float_optional(value, numbers);
+                    }
+                    Some("operator_name") => {
                         operator_name = Some(value.to_string());
                     }
-                    "mcc-mnc" | "operator_code" => {
+                    Some("operator_code") => {
                         operator_code = Some(value.to_string());
                     }
-                    "cgi" => {
+                    Some("cgi") => {
                         cgi = Some(value.to_string());
                     }
-                    "cellname" => {
+                    Some("cellname") => {
                         cellname = Some(value.to_string());
                     }
-                    "node" | "rnc" | "enodeb" => {
+                    Some("node") => {
                         node = Some(value.to_string());
                     }
-                    "cellid" | "cell_id" => {
+                    Some("cell_id") => {
                         cell_id = Some(value.to_string());
                     }
-                    "lac" => {
+                    Some("lac") => {
                         lac = Some(value.to_string());
                     }
-                    "networktech" | "network_tech" | "tech" => {
+                    Some("network_tech") => {
                         network_tech = Some(value.to_string());
                     }
-                    "networkmode" | "network_mode" | "mode" => {
+                    Some("network_mode") => {
                         network_mode = Some(value.to_string());
                     }
-                    "level" | "rsrp" | "rscp" | "rxlevel" => {
-                        level = parse_float_optional(value);
+                    Some("level") => {
+                        level = parse_float_optional(value, numbers);
                     }
-                    "qual" | "rsrq" | "ecno" | "rxqual" => {
-                        qual = parse_float_optional(value);
+                    Some("qual") => {
+                        qual = parse_float_optional(value, numbers);
                     }
-                    "snr" => {
-                        snr = parse_float_optional(value);
+                    Some("snr") => {
+                        snr = parse_float_optional(value, numbers);
                     }
-                    "cqi" => {
-                        cqi = parse_float_optional(value);
+                    Some("cqi") => {
+                        cqi = parse_float_optional(value, numbers);
                     }
-                    "arfcn" => {
+                    Some("arfcn") => {
                         arfcn = Some(value.to_string());
                     }
-                    "dl_bitrate" | "downlink_bitrate" => {
-                        dl_bitrate = parse_float_optional(value);
+                    Some("dl_bitrate") => {
+                        dl_bitrate = parse_float_optional(value, numbers);
+                    }
+                    Some("ul_bitrate") => {
+                        ul_bitrate = parse_float_optional(value, numbers);
+                    }
+                    Some("ping_avg") => {
+                        ping_avg = parse_ping_optional(value, numbers);
+                    }
+                    Some("ping_min") => {
+                        ping_min = parse_ping_optional(value, numbers);
                     }
-                    "ul_bitrate" | "uplink_bitrate" => {
-                        ul_bitrate = parse_float_optional(value);
+                    Some("ping_max") => {
+                        ping_max = parse_ping_optional(value, numbers);
+                    }
+                    Some("ping_loss") => {
+                        ping_loss = parse_ping_optional(value, numbers);
                     }
                     _ => {
-                        // Ignore unknown columns
-                        debug!("Unknown column: {header}");
+                        if let Some(mapping) = column_map.get(&header_lower) {
+                            if let Some(value) = parse_mapped_value(value, mapping.kind) {
+                                extra.insert(mapping.name.clone(), value);
+                            }
+                        } else if keep_unknown {
+                            if let Some(key) = sanitize_field_key(header)
+                                && !value.trim().is_empty()
+                            {
+                                extra.insert(key, FieldValue::Text(value.to_string()));
+                            }
+                        } else {
+                            // Ignore unknown columns
+                            debug!("Unknown column: {header}");
+                        }
                     }
                 }
             }
@@ -133,9 +205,12 @@ impl GNetTrackRecord {
             timestamp,
             longitude,
             latitude,
+            altitude,
             speed,
             operator_name,
             operator_code,
+            mcc: None,
+            mnc: None,
             cgi,
             cellname,
             node,
@@ -150,124 +225,1180 @@ impl GNetTrackRecord {
             arfcn,
             dl_bitrate,
             ul_bitrate,
+            ping_avg,
+            ping_min,
+            ping_max,
+            ping_loss,
+            distance_m: None,
+            bearing_deg: None,
+            signal_quality: None,
+            gps_interpolated: false,
+            extra,
         })
     }
 }
 
+/// Record field a known column is read into, from its lowercased header.
+fn target_field(header_lower: &str) -> Option<&'static str> {
+    let field = match header_lower {
+        "timestamp" | "time" => "timestamp",
+        "longitude" | "lon" => "longitude",
+        "latitude" | "lat" => "latitude",
+        "altitude" | "alt" => "altitude",
+        "speed" => "speed",
+        "operator" | "operator_name" => "operator_name",
+        "mcc-mnc" | "operator_code" => "operator_code",
+        "cgi" => "cgi",
+        "cellname" => "cellname",
+        "node" | "rnc" | "enodeb" => "node",
+        "cellid" | "cell_id" => "cell_id",
+        "lac" => "lac",
+        "networktech" | "network_tech" | "tech" => "network_tech",
+        "networkmode" | "network_mode" | "mode" => "network_mode",
+        "level" | "rsrp" | "rscp" | "rxlevel" => "level",
+        "qual" | "rsrq" | "ecno" | "rxqual" => "qual",
+        "snr" => "snr",
+        "cqi" => "cqi",
+        "arfcn" => "arfcn",
+        "dl_bitrate" | "downlink_bitrate" => "dl_bitrate",
+        "ul_bitrate" | "uplink_bitrate" => "ul_bitrate",
+        "pingavg" | "ping_avg" => "ping_avg",
+        "pingmin" | "ping_min" => "ping_min",
+        "pingmax" | "ping_max" => "ping_max",
+        "pingloss" | "ping_loss" => "ping_loss",
+        _ => return None,
+    };
+    Some(field)
+}
+
+/// Blanks all but one of the columns that read into the same record field, e.g. `Level`
+/// and `RxLevel`, warning once per field. The column listed first in `priority` (lowercase
+/// headers) wins; without one, the last column does, as it would by overwriting.
+fn resolve_header_conflicts(headers: &StringRecord, priority: &[String]) -> StringRecord {
+    let mut by_field: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, header) in headers.iter().enumerate() {
+        if let Some(field) = target_field(&header.to_lowercase()) {
+            by_field.entry(field).or_default().push(i);
+        }
+    }
+
+    let mut dropped = HashSet::new();
+    for (field, columns) in by_field.into_iter().filter(|(_, c)| c.len() > 1) {
+        let rank = |i: &usize| {
+            let header = headers[*i].to_lowercase();
+            priority.iter().position(|p| *p == header)
+        };
+        let winner = columns
+            .iter()
+            .filter(|i| rank(i).is_some())
+            .min_by_key(|i| rank(i))
+            .or(columns.last())
+            .copied()
+            .unwrap_or_default();
+        let names: Vec<&str> = columns.iter().map(|i| &headers[*i]).collect();
+        warn!(
+            "Columns {} all map to {field}; using {} (set processing.header_priority to choose)",
+            names.join(", "),
+            &headers[winner]
+        );
+        dropped.extend(columns.into_iter().filter(|i| *i != winner));
+    }
+
+    headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| if dropped.contains(&i) { "" } else { header })
+        .collect()
+}
+
+/// Field key for an unknown column: the lowercased header with runs of anything but ASCII
+/// letters and digits replaced by `_`, e.g. `CA Band` becomes `ca_band`. Leading
+/// underscores are dropped since InfluxDB reserves them. `None` if nothing is left.
+fn sanitize_field_key(header: &str) -> Option<String> {
+    let mut key = String::with_capacity(header.len());
+    for c in header.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        } else if !key.is_empty() && !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+    let key = key.trim_end_matches('_');
+    (!key.is_empty()).then(|| key.to_string())
+}
+
+/// Records parsed from one input, and how many invalid records or lines were skipped
+/// with `skip_invalid`.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOutcome {
+    pub records: Vec<GNetTrackRecord>,
+    pub skipped: usize,
+}
+
 pub struct LogParser {
+    batch_size: usize,
     skip_invalid: bool,
+    timezone: FixedOffset,
+    columns: Option<Vec<String>>,
+    column_map: HashMap<String, ColumnMapping>,
+    keep_unknown_columns: bool,
+    header_priority: Vec<String>,
+    speed_unit: SpeedUnit,
+    numbers: NumberFormat,
+    comment_char: Option<u8>,
+    parallel: bool,
+}
+
+/// Cell values treated as missing numbers unless `processing.null_tokens` says otherwise.
+pub const DEFAULT_NULL_TOKENS: &[&str] = &["-", "--", "---", "n/a", "na", "nan", "null", "none"];
+
+/// How numeric CSV cells are read.
+#[derive(Debug, Clone)]
+pub struct NumberFormat {
+    /// Lowercase values read as missing
+    pub null_tokens: HashSet<String>,
+    /// Read a single comma as the decimal separator, e.g. `-95,3`
+    pub decimal_comma: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            null_tokens: DEFAULT_NULL_TOKENS.iter().map(|t| t.to_string()).collect(),
+            decimal_comma: false,
+        }
+    }
 }
 
 impl LogParser {
-    pub fn new(_batch_size: usize, skip_invalid: bool) -> Self {
-        Self { skip_invalid }
+    /// A `batch_size` of 0 falls back to the default.
+    pub fn new(batch_size: usize, skip_invalid: bool) -> Self {
+        let batch_size = if batch_size == 0 {
+            warn!("batch_size of 0 is invalid, using {DEFAULT_BATCH_SIZE}");
+            DEFAULT_BATCH_SIZE
+        } else {
+            batch_size
+        };
+        Self {
+            batch_size,
+            skip_invalid,
+            timezone: utc_offset(),
+            columns: None,
+            column_map: HashMap::new(),
+            keep_unknown_columns: false,
+            header_priority: Vec::new(),
+            speed_unit: SpeedUnit::default(),
+            numbers: NumberFormat::default(),
+            comment_char: Some(b'#'),
+            parallel: false,
+        }
+    }
+
+    /// Records per upload batch the parser was configured with.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Skips lines starting with `comment_char`, including ones before the header. `None`
+    /// reads every line.
+    pub fn with_comment_char(mut self, comment_char: Option<u8>) -> Self {
+        self.comment_char = comment_char;
+        self
+    }
+
+    /// Reads numbers like `-95,3` as `-95.3`. Values with a dot or several commas are left as is.
+    pub fn with_decimal_comma(mut self, decimal_comma: bool) -> Self {
+        self.numbers.decimal_comma = decimal_comma;
+        self
+    }
+
+    /// Replaces the values read as missing numbers. Matching ignores case.
+    pub fn with_null_tokens(mut self, null_tokens: &[String]) -> Self {
+        self.numbers.null_tokens = null_tokens
+            .iter()
+            .map(|token| token.trim().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Treats the input as header-less, using `columns` as the column names.
+    pub fn with_columns(mut self, columns: Option<Vec<String>>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Keeps the unknown columns named in `column_map`. Header names match case-insensitively.
+    pub fn with_column_map(mut self, column_map: &HashMap<String, ColumnMapping>) -> Self {
+        self.column_map = column_map
+            .iter()
+            .map(|(header, mapping)| (header.to_lowercase(), mapping.clone()))
+            .collect();
+        self
+    }
+
+    /// Keeps columns that are neither known nor in `column_map` as string fields instead of
+    /// dropping them.
+    pub fn with_keep_unknown_columns(mut self, keep: bool) -> Self {
+        self.keep_unknown_columns = keep;
+        self
+    }
+
+    /// Headers that win when several columns read into the same field, most preferred first.
+    /// Matching ignores case.
+    pub fn with_header_priority(mut self, priority: &[String]) -> Self {
+        self.header_priority = priority
+            .iter()
+            .map(|header| header.trim().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Sets the unit of the speed column. Speeds are converted to km/h.
+    pub fn with_speed_unit(mut self, speed_unit: SpeedUnit) -> Self {
+        self.speed_unit = speed_unit;
+        self
+    }
+
+    /// Converts rows to records on a rayon thread pool once the file has been read.
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Sets the timezone that naive (offset-less) timestamps are recorded in.
+    pub fn with_timezone(mut self, timezone: FixedOffset) -> Self {
+        self.timezone = timezone;
+        self
     }
 
     pub fn parse_file(&self, file_path: &str) -> Result<Vec<GNetTrackRecord>> {
-        let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
+        self.parse_reader(open_input(file_path)?, Delimiter::Auto)
+    }
+
+    /// Parses CSV data from any reader. With `Delimiter::Auto` the header line is
+    /// buffered to sniff the delimiter and then replayed to the CSV reader.
+    pub fn parse_reader<R: Read>(
+        &self,
+        reader: R,
+        format_hint: Delimiter,
+    ) -> Result<Vec<GNetTrackRecord>> {
+        Ok(self.parse_reader_outcome(reader, format_hint)?.records)
+    }
+
+    /// Like `parse_reader`, also counting the rows skipped as invalid.
+    pub fn parse_reader_outcome<R: Read>(
+        &self,
+        reader: R,
+        format_hint: Delimiter,
+    ) -> Result<ParseOutcome> {
+        let mut records = Vec::new();
+        let (_, skipped) = self.process_reader_counted(reader, format_hint, |record| {
+            records.push(record);
+            Ok(())
+        })?;
+        Ok(ParseOutcome { records, skipped })
+    }
+
+    /// Streaming form of `parse_reader`. An error returned by `f` stops parsing.
+    pub fn process_reader<R, F>(&self, reader: R, format_hint: Delimiter, f: F) -> Result<usize>
+    where
+        R: Read,
+        F: FnMut(GNetTrackRecord) -> Result<()>,
+    {
+        Ok(self.process_reader_counted(reader, format_hint, f)?.0)
+    }
+
+    /// `process_reader` returning the number of records passed to `f` and of skipped rows.
+    fn process_reader_counted<R, F>(
+        &self,
+        reader: R,
+        format_hint: Delimiter,
+        mut f: F,
+    ) -> Result<(usize, usize)>
+    where
+        R: Read,
+        F: FnMut(GNetTrackRecord) -> Result<()>,
+    {
+        let mut reader = BufReader::new(reader);
+        // The header (or first row) is the first line that is neither blank nor a comment
+        let mut first_line = String::new();
+        let mut skipped_lines = 0;
+        while reader.read_line(&mut first_line)? > 0 {
+            let line = first_line.trim_start_matches('\u{feff}').trim();
+            let is_comment = self
+                .comment_char
+                .is_some_and(|c| line.as_bytes().first() == Some(&c));
+            if !line.is_empty() && !is_comment {
+                break;
+            }
+            skipped_lines += 1;
+            first_line.clear();
+        }
+
+        // Windows exports often start with a UTF-8 BOM, which would otherwise end up
+        // in the first header name
+        if let Some(stripped) = first_line.strip_prefix('\u{feff}') {
+            first_line = stripped.to_string();
+        }
 
-        // Try to determine the delimiter (tab or comma)
-        let delimiter = self.detect_delimiter(file_path)?;
+        let delimiter = match format_hint {
+            Delimiter::Auto => detect_delimiter(&first_line),
+            hint => hint,
+        };
+        debug!("Using delimiter: {delimiter:?}");
 
         let mut csv_reader = ReaderBuilder::new()
-            .delimiter(delimiter)
-            .has_headers(true)
-            .from_reader(reader);
+            .delimiter(delimiter.as_byte())
+            .quoting(true)
+            .quote(b'"')
+            .double_quote(true)
+            .has_headers(self.columns.is_none())
+            .comment(self.comment_char)
+            .from_reader(Cursor::new(first_line).chain(reader));
 
-        let headers = csv_reader.headers()?.clone();
-        let mut records = Vec::new();
+        // Header-less files get their header record from the configured column order
+        let (headers, first_data_line) = match &self.columns {
+            Some(columns) => (StringRecord::from(columns.clone()), 1),
+            None => (csv_reader.headers()?.clone(), 2),
+        };
+        // An unrelated CSV would otherwise yield empty records stamped with the current time
+        if self.column_map.is_empty()
+            && !headers.is_empty()
+            && !headers
+                .iter()
+                .any(|header| target_field(&header.to_lowercase()).is_some())
+        {
+            return Err(anyhow!(
+                "None of the columns ({}) is a known G-NetTrack column; the file may not be a \
+                 G-NetTrack log, or its columns can be mapped with processing.column_map",
+                headers.iter().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        let headers = resolve_header_conflicts(&headers, &self.header_priority);
+        let mut processed = 0;
         let mut error_count = 0;
+        // With `parallel`, rows are only read here and converted on the rayon pool afterwards
+        let mut pending = Vec::new();
+
+        for (index, result) in csv_reader.records().enumerate() {
+            // Positions skip comment and blank lines, so prefer them over counting rows
+            let position = match &result {
+                Ok(record) => record.position(),
+                Err(e) => e.position(),
+            };
+            let line_num = skipped_lines
+                + position.map_or(index as u64 + first_data_line, |p| p.line()) as usize;
+            if index == 0
+                && self.columns.is_some()
+                && let Ok(ref record) = result
+                && record.len() != headers.len()
+            {
+                return Err(anyhow!(
+                    "Configured columns ({}) do not match the {} values in the first data row",
+                    headers.len(),
+                    record.len()
+                ));
+            }
 
-        for (line_num, result) in csv_reader.records().enumerate() {
             match result {
-                Ok(record) => match GNetTrackRecord::from_csv_record(&record, &headers) {
-                    Ok(parsed_record) => {
-                        records.push(parsed_record);
-                    }
-                    Err(e) => {
-                        error_count += 1;
-                        if self.skip_invalid {
-                            warn!("Skipping invalid record at line {}: {}", line_num + 2, e);
-                        } else {
-                            return Err(anyhow!(
-                                "Error parsing record at line {}: {}",
-                                line_num + 2,
-                                e
-                            ));
-                        }
-                    }
-                },
+                Ok(record) if self.parallel => pending.push((line_num, record)),
+                Ok(record) => {
+                    let converted = self.convert_record(&record, &headers);
+                    self.handle_converted(
+                        line_num,
+                        converted,
+                        &mut f,
+                        &mut processed,
+                        &mut error_count,
+                    )?;
+                }
                 Err(e) => {
                     error_count += 1;
                     if self.skip_invalid {
-                        warn!("Skipping malformed line {}: {}", line_num + 2, e);
+                        warn!("Skipping malformed line {}: {}", line_num, e);
                     } else {
-                        return Err(anyhow!("Error reading line {}: {}", line_num + 2, e));
+                        return Err(anyhow!("Error reading line {}: {}", line_num, e));
                     }
                 }
             }
         }
 
+        if self.parallel {
+            // `collect` on an indexed parallel iterator keeps the input order
+            let converted: Vec<_> = pending
+                .par_iter()
+                .map(|(line_num, record)| (*line_num, self.convert_record(record, &headers)))
+                .collect();
+            for (line_num, converted) in converted {
+                self.handle_converted(
+                    line_num,
+                    converted,
+                    &mut f,
+                    &mut processed,
+                    &mut error_count,
+                )?;
+            }
+        }
+
         if error_count > 0 {
             warn!("Encountered {error_count} errors while parsing file");
         }
 
-        Ok(records)
+        Ok((processed, error_count))
     }
 
-    fn detect_delimiter(&self, file_path: &str) -> Result<u8> {
-        let file = File::open(file_path)?;
-        let mut reader = BufReader::new(file);
-        let mut line = String::new();
+    fn convert_record(
+        &self,
+        record: &StringRecord,
+        headers: &StringRecord,
+    ) -> Result<GNetTrackRecord> {
+        let mut record = GNetTrackRecord::from_csv_record(
+            record,
+            headers,
+            &self.timezone,
+            &self.column_map,
+            &self.numbers,
+            self.keep_unknown_columns,
+        )?;
+        record.speed = record.speed.map(|speed| self.speed_unit.to_kmh(speed));
+        Ok(record)
+    }
 
-        use std::io::BufRead;
-        reader.read_line(&mut line)?;
+    /// Passes a converted row to `f`, or applies `skip_invalid` if conversion failed.
+    fn handle_converted<F>(
+        &self,
+        line_num: usize,
+        converted: Result<GNetTrackRecord>,
+        f: &mut F,
+        processed: &mut usize,
+        error_count: &mut usize,
+    ) -> Result<()>
+    where
+        F: FnMut(GNetTrackRecord) -> Result<()>,
+    {
+        match converted {
+            Ok(record) => {
+                f(record)?;
+                *processed += 1;
+            }
+            Err(e) => {
+                *error_count += 1;
+                if self.skip_invalid {
+                    warn!("Skipping invalid record at line {}: {}", line_num, e);
+                } else {
+                    return Err(anyhow!("Error parsing record at line {}: {}", line_num, e));
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
-        if line.contains('\t') {
-            Ok(b'\t')
-        } else {
-            Ok(b',')
+/// Field delimiter of a CSV input. `Auto` detects it from the header line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Auto,
+    Tab,
+    Semicolon,
+    Comma,
+}
+
+impl Delimiter {
+    fn as_byte(self) -> u8 {
+        match self {
+            Delimiter::Tab => b'\t',
+            Delimiter::Semicolon => b';',
+            Delimiter::Comma | Delimiter::Auto => b',',
+        }
+    }
+}
+
+/// Picks the most frequent of tab, semicolon and comma in the header line.
+/// Ties prefer tab, then semicolon.
+fn detect_delimiter(header_line: &str) -> Delimiter {
+    let candidates = [
+        (Delimiter::Tab, '\t'),
+        (Delimiter::Semicolon, ';'),
+        (Delimiter::Comma, ','),
+    ];
+
+    let mut best = Delimiter::Comma;
+    let mut best_count = 0;
+    for (delimiter, c) in candidates {
+        let count = header_line.matches(c).count();
+        if count > best_count {
+            best = delimiter;
+            best_count = count;
         }
     }
+    best
 }
 
-fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+/// Parses the timestamp cell `value` of `column`; errors name both so a broken row can be found.
+fn parse_timestamp(value: &str, column: &str, timezone: &FixedOffset) -> Result<DateTime<Utc>> {
     if value.is_empty() {
         return Ok(Utc::now());
     }
 
-    // Try multiple timestamp formats
+    // RFC3339 / ISO8601 with an explicit offset, e.g. 2025-10-03T10:20:09+09:00
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    // Epoch milliseconds (13 digits) and microseconds (16 digits)
+    if let Ok(epoch) = value.parse::<i64>() {
+        let digits = value.trim_start_matches('-').len();
+        let parsed = match digits {
+            13 => DateTime::from_timestamp_millis(epoch),
+            16 => DateTime::from_timestamp_micros(epoch),
+            _ => None,
+        };
+        if let Some(dt) = parsed {
+            return Ok(dt);
+        }
+    }
+
+    // Formats that explicitly mark the time as UTC
+    let utc_formats = ["%Y-%m-%dT%H:%M:%SZ", "%Y-%m-%dT%H:%M:%S%.3fZ"];
+
+    for format in &utc_formats {
+        if let Ok(naive_dt) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(DateTime::from_naive_utc_and_offset(naive_dt, Utc));
+        }
+    }
+
+    // Naive formats are wall-clock time in the configured input timezone
     let formats = [
         "%Y-%m-%d %H:%M:%S",
         "%Y/%m/%d %H:%M:%S",
         "%d.%m.%Y %H:%M:%S",
         "%Y-%m-%d %H:%M:%S%.3f",
         "%Y-%m-%dT%H:%M:%S",
-        "%Y-%m-%dT%H:%M:%SZ",
-        "%Y-%m-%dT%H:%M:%S%.3fZ",
     ];
 
     for format in &formats {
         if let Ok(naive_dt) = NaiveDateTime::parse_from_str(value, format) {
-            return Ok(DateTime::from_naive_utc_and_offset(naive_dt, Utc));
+            return local_to_utc(naive_dt, timezone);
         }
     }
 
-    // Try parsing as Unix timestamp
+    // Try parsing as Unix timestamp in seconds
     if let Ok(timestamp) = value.parse::<i64>()
         && let Some(dt) = DateTime::from_timestamp(timestamp, 0)
     {
         return Ok(dt);
     }
 
-    Err(anyhow!("Unable to parse timestamp: {value}"))
+    Err(anyhow!(
+        "Failed to parse timestamp in column '{column}': '{value}'"
+    ))
+}
+
+/// Converts a naive wall-clock time recorded in `timezone` to UTC.
+pub fn local_to_utc(naive_dt: NaiveDateTime, timezone: &FixedOffset) -> Result<DateTime<Utc>> {
+    timezone
+        .from_local_datetime(&naive_dt)
+        .single()
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("Invalid local time: {naive_dt}"))
+}
+
+/// Parses a timezone setting such as `UTC`, `+09:00` or `-0530` into a fixed offset.
+pub fn parse_timezone(value: &str) -> Result<FixedOffset> {
+    let trimmed = value.trim();
+    if trimmed.eq_ignore_ascii_case("utc") || trimmed == "Z" {
+        return Ok(utc_offset());
+    }
+
+    let (sign, rest) = match trimmed.chars().next() {
+        Some('+') => (1, &trimmed[1..]),
+        Some('-') => (-1, &trimmed[1..]),
+        _ => {
+            return Err(anyhow!(
+                "Invalid timezone '{value}', expected UTC or +HH:MM"
+            ));
+        }
+    };
+    let digits = rest.replace(':', "");
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok(), Some(0)),
+        4 => (
+            digits[..2].parse::<i32>().ok(),
+            digits[2..].parse::<i32>().ok(),
+        ),
+        _ => (None, None),
+    };
+
+    match (hours, minutes) {
+        (Some(hours), Some(minutes)) if minutes < 60 => {
+            FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+                .ok_or_else(|| anyhow!("Timezone offset out of range: {value}"))
+        }
+        _ => Err(anyhow!(
+            "Invalid timezone '{value}', expected UTC or +HH:MM"
+        )),
+    }
+}
+
+pub fn utc_offset() -> FixedOffset {
+    FixedOffset::east_opt(0).unwrap()
+}
+
+/// Converts a mapped column value to its configured type. Empty or unparsable values are dropped.
+fn parse_mapped_value(value: &str, kind: ColumnType) -> Option<FieldValue> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    match kind {
+        ColumnType::Tag | ColumnType::StringField => Some(FieldValue::Text(value.to_string())),
+        ColumnType::FloatField => value.parse().ok().map(FieldValue::Float),
+        ColumnType::IntegerField => value.parse().ok().map(FieldValue::Integer),
+    }
+}
+
+/// Like `parse_float_optional`, but maps G-NetTrack's `-1` "no result" value to `None`.
+fn parse_ping_optional(value: &str, numbers: &NumberFormat) -> Option<f64> {
+    parse_float_optional(value, numbers).filter(|v| *v != -1.0)
 }
 
-fn parse_float_optional(value: &str) -> Option<f64> {
-    if value.is_empty() || value == "N/A" || value == "null" {
-        None
+fn parse_float_optional(value: &str, numbers: &NumberFormat) -> Option<f64> {
+    let value = value.trim();
+    if value.is_empty() || numbers.null_tokens.contains(&value.to_lowercase()) {
+        return None;
+    }
+
+    let parsed = if numbers.decimal_comma && value.matches(',').count() == 1 && !value.contains('.')
+    {
+        value.replace(',', ".").parse()
     } else {
-        value.parse().ok()
+        value.parse()
+    };
+    // NaN and infinity can't be written as InfluxDB fields
+    parsed.ok().filter(|v: &f64| v.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(csv: &str) -> Vec<GNetTrackRecord> {
+        LogParser::new(1000, false)
+            .parse_reader(csv.as_bytes(), Delimiter::Auto)
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_csv_from_a_reader() {
+        let records = parse(
+            "Timestamp\tLongitude\tLatitude\tOperator\tCellID\tLevel\tSpeed\n\
+             2025-10-03 10:20:09\t139.5\t35.7\tNTT docomo\t1234\t-95\t12.5\n\
+             2025-10-03 10:20:10\t139.6\t35.8\tNTT docomo\t1235\t-96\t\n",
+        );
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].timestamp.to_rfc3339(),
+            "2025-10-03T10:20:09+00:00"
+        );
+        assert_eq!(records[0].longitude, Some(139.5));
+        assert_eq!(records[0].latitude, Some(35.7));
+        assert_eq!(records[0].operator_name.as_deref(), Some("NTT docomo"));
+        assert_eq!(records[0].cell_id.as_deref(), Some("1234"));
+        assert_eq!(records[0].level, Some(-95.0));
+        assert_eq!(records[0].speed, Some(12.5));
+        assert_eq!(records[1].speed, None);
+    }
+
+    #[test]
+    fn naive_timestamps_use_the_input_timezone() {
+        let jst = parse_timezone("+09:00").unwrap();
+        let records = LogParser::new(1000, false)
+            .with_timezone(jst)
+            .parse_reader(
+                "Timestamp,Level\n2025-10-03 19:20:09,-95\n2025-10-03T19:20:09+00:00,-96\n"
+                    .as_bytes(),
+                Delimiter::Auto,
+            )
+            .unwrap();
+        assert_eq!(
+            records[0].timestamp.to_rfc3339(),
+            "2025-10-03T10:20:09+00:00"
+        );
+        // An explicit offset wins over the configured timezone
+        assert_eq!(
+            records[1].timestamp.to_rfc3339(),
+            "2025-10-03T19:20:09+00:00"
+        );
+    }
+
+    #[test]
+    fn parses_timezone_settings() {
+        assert_eq!(parse_timezone("UTC").unwrap().local_minus_utc(), 0);
+        assert_eq!(
+            parse_timezone("+09:00").unwrap().local_minus_utc(),
+            9 * 3600
+        );
+        assert_eq!(parse_timezone("+0900").unwrap().local_minus_utc(), 9 * 3600);
+        assert_eq!(
+            parse_timezone("-05:30").unwrap().local_minus_utc(),
+            -(5 * 3600 + 30 * 60)
+        );
+        assert!(parse_timezone("JST").is_err());
+        assert!(parse_timezone("+09:75").is_err());
+    }
+
+    fn timestamp(value: &str) -> String {
+        parse_timestamp(value, "Timestamp", &utc_offset())
+            .unwrap()
+            .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+    }
+
+    #[test]
+    fn parses_each_timestamp_format() {
+        let expected = "2025-10-03T10:20:09Z";
+        for value in [
+            "2025-10-03T10:20:09Z",
+            "2025-10-03T19:20:09+09:00",
+            "2025-10-03T04:50:09-05:30",
+            "2025-10-03 10:20:09",
+            "2025/10/03 10:20:09",
+            "03.10.2025 10:20:09",
+            "2025-10-03T10:20:09",
+            "1759486809",
+        ] {
+            assert_eq!(timestamp(value), expected, "{value}");
+        }
+        assert_eq!(
+            timestamp("2025-10-03T10:20:09.250Z"),
+            "2025-10-03T10:20:09.250Z"
+        );
+        assert_eq!(
+            timestamp("2025-10-03 10:20:09.250"),
+            "2025-10-03T10:20:09.250Z"
+        );
+        assert_eq!(timestamp("1759486809250"), "2025-10-03T10:20:09.250Z");
+        assert_eq!(timestamp("1759486809250000"), "2025-10-03T10:20:09.250Z");
+    }
+
+    #[test]
+    fn parses_negative_epochs_and_fractional_offsets() {
+        assert_eq!(timestamp("-1000"), "1969-12-31T23:43:20Z");
+        assert_eq!(timestamp("-1000000000000"), "1938-04-24T22:13:20Z");
+        assert_eq!(
+            timestamp("2025-10-03T16:05:09.5+05:45"),
+            "2025-10-03T10:20:09.500Z"
+        );
+    }
+
+    #[test]
+    fn detects_the_delimiter_from_the_header() {
+        assert_eq!(detect_delimiter("Timestamp\tLevel\tSpeed"), Delimiter::Tab);
+        assert_eq!(
+            detect_delimiter("Timestamp;Level;Speed"),
+            Delimiter::Semicolon
+        );
+        assert_eq!(detect_delimiter("Timestamp,Level,Speed"), Delimiter::Comma);
+        assert_eq!(detect_delimiter("Timestamp"), Delimiter::Comma);
+
+        for csv in [
+            "Timestamp;Level;Speed\n2025-10-03 10:20:09;-95;12\n",
+            "Timestamp\tLevel\tSpeed\n2025-10-03 10:20:09\t-95\t12\n",
+        ] {
+            let records = parse(csv);
+            assert_eq!(records[0].level, Some(-95.0), "{csv}");
+            assert_eq!(records[0].speed, Some(12.0), "{csv}");
+        }
+    }
+
+    #[test]
+    fn reads_quoted_fields_with_delimiters_and_quotes() {
+        let records = parse(
+            "Timestamp,Operator,CellName,Level\n\
+             2025-10-03 10:20:09,\"au, KDDI\",\"Shibuya \"\"East\"\", 2\",-95\n",
+        );
+        assert_eq!(records[0].operator_name.as_deref(), Some("au, KDDI"));
+        assert_eq!(records[0].cellname.as_deref(), Some("Shibuya \"East\", 2"));
+        assert_eq!(records[0].level, Some(-95.0));
+    }
+
+    #[test]
+    fn strips_a_bom_from_the_first_header() {
+        let records = parse("\u{feff}Timestamp,Level\n2025-10-03 10:20:09,-95\n");
+        assert_eq!(
+            records[0].timestamp.to_rfc3339(),
+            "2025-10-03T10:20:09+00:00"
+        );
+        assert_eq!(records[0].level, Some(-95.0));
+    }
+
+    #[test]
+    fn reads_header_less_files_with_configured_columns() {
+        let columns = ["Timestamp", "Level", "Speed"].map(str::to_string).to_vec();
+        let parser = LogParser::new(1000, false).with_columns(Some(columns));
+        let records = parser
+            .parse_reader(
+                "2025-10-03 10:20:09,-95,12\n2025-10-03 10:20:10,-96,13\n".as_bytes(),
+                Delimiter::Auto,
+            )
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].timestamp.to_rfc3339(),
+            "2025-10-03T10:20:09+00:00"
+        );
+        assert_eq!(records[1].level, Some(-96.0));
+        assert_eq!(records[1].speed, Some(13.0));
+
+        let error = parser
+            .parse_reader("2025-10-03 10:20:09,-95\n".as_bytes(), Delimiter::Auto)
+            .unwrap_err();
+        assert!(error.to_string().contains("do not match"), "{error}");
+    }
+
+    fn column_map() -> HashMap<String, ColumnMapping> {
+        HashMap::from([
+            (
+                "PCI".to_string(),
+                ColumnMapping {
+                    name: "pci".to_string(),
+                    kind: ColumnType::IntegerField,
+                },
+            ),
+            (
+                "BAND".to_string(),
+                ColumnMapping {
+                    name: "band".to_string(),
+                    kind: ColumnType::Tag,
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn keeps_mapped_columns() {
+        let records = LogParser::new(1000, false)
+            .with_column_map(&column_map())
+            .parse_reader(
+                "Timestamp,Level,pci,Band,Other\n2025-10-03 10:20:09,-95,312,B1,x\n\
+                 2025-10-03 10:20:10,-96,n/a,,x\n"
+                    .as_bytes(),
+                Delimiter::Auto,
+            )
+            .unwrap();
+        assert_eq!(
+            records[0].extra,
+            HashMap::from([
+                ("pci".to_string(), FieldValue::Integer(312)),
+                ("band".to_string(), FieldValue::Text("B1".to_string())),
+            ])
+        );
+        assert!(records[1].extra.is_empty());
+    }
+
+    #[test]
+    fn process_reader_calls_back_once_per_valid_row() {
+        let csv = "Timestamp,Level\n2025-10-03 10:20:09,-95\nnot a time,-96\n\
+                   2025-10-03 10:20:11,-97\n";
+        let mut levels = Vec::new();
+        let processed = LogParser::new(1000, true)
+            .process_reader(csv.as_bytes(), Delimiter::Auto, |record| {
+                levels.push(record.level);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(processed, 2);
+        assert_eq!(levels, [Some(-95.0), Some(-97.0)]);
+
+        let mut calls = 0;
+        let error = LogParser::new(1000, false)
+            .process_reader(csv.as_bytes(), Delimiter::Auto, |_| {
+                calls += 1;
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!(calls, 1);
+        assert!(error.to_string().contains("line 3"), "{error}");
+    }
+
+    #[test]
+    fn process_reader_stops_on_callback_errors() {
+        let csv = "Timestamp,Level\n2025-10-03 10:20:09,-95\n2025-10-03 10:20:10,-96\n";
+        let mut calls = 0;
+        let result =
+            LogParser::new(1000, true).process_reader(csv.as_bytes(), Delimiter::Auto, |_| {
+                calls += 1;
+                Err(anyhow!("upload failed"))
+            });
+        assert_eq!(result.unwrap_err().to_string(), "upload failed");
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn parallel_parsing_keeps_the_input_order() {
+        let mut csv = String::from("Timestamp,CellID,Level\n");
+        for i in 0..500u32 {
+            // Times jump back and forth so any reordering would show
+            let second = (i * 37) % 60;
+            let cell = (i * 7919) % 1000;
+            if i % 97 == 0 {
+                csv.push_str(&format!("broken,{cell},-{}\n", i % 50));
+            } else {
+                csv.push_str(&format!(
+                    "2025-10-03 10:20:{second:02},{cell},-{}\n",
+                    i % 50
+                ));
+            }
+        }
+        let parse = |parallel: bool| {
+            LogParser::new(1000, true)
+                .with_parallel(parallel)
+                .parse_reader_outcome(csv.as_bytes(), Delimiter::Auto)
+                .unwrap()
+        };
+        let sequential = parse(false);
+        let parallel = parse(true);
+
+        assert_eq!(parallel.skipped, sequential.skipped);
+        assert_eq!(parallel.skipped, 6);
+        let key =
+            |record: &GNetTrackRecord| (record.timestamp, record.cell_id.clone(), record.level);
+        assert_eq!(
+            parallel.records.iter().map(key).collect::<Vec<_>>(),
+            sequential.records.iter().map(key).collect::<Vec<_>>()
+        );
+        assert!(
+            LogParser::new(1000, false)
+                .with_parallel(true)
+                .parse_reader(csv.as_bytes(), Delimiter::Auto)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn reads_ping_columns_and_drops_the_no_result_sentinel() {
+        let records = parse(
+            "Timestamp,PINGAVG,PINGMIN,PINGMAX,PINGLOSS\n\
+             2025-10-03 10:20:09,42.5,30,61,0\n\
+             2025-10-03 10:20:10,-1,-1,-1,100\n",
+        );
+        let pings = |r: &GNetTrackRecord| (r.ping_avg, r.ping_min, r.ping_max, r.ping_loss);
+        assert_eq!(
+            pings(&records[0]),
+            (Some(42.5), Some(30.0), Some(61.0), Some(0.0))
+        );
+        assert_eq!(pings(&records[1]), (None, None, None, Some(100.0)));
+    }
+
+    #[test]
+    fn reads_gzip_compressed_files_like_plain_ones() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        let csv = "Timestamp\tLongitude\tLatitude\tLevel\tCellname\n\
+                   2025-10-03 10:20:09\t139.5\t35.7\t-95\tA\n\
+                   2025-10-03 10:20:10\t139.6\t35.8\t-96\tB\n";
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("drive.txt");
+        let gzip = dir.path().join("drive.txt.gz");
+        std::fs::write(&plain, csv).unwrap();
+        let mut encoder = GzEncoder::new(
+            std::fs::File::create(&gzip).unwrap(),
+            Compression::default(),
+        );
+        encoder.write_all(csv.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let parser = LogParser::new(1000, false);
+        let as_json = |path: &std::path::Path| {
+            serde_json::to_value(parser.parse_file(path.to_str().unwrap()).unwrap()).unwrap()
+        };
+        let records = as_json(&plain);
+        assert_eq!(records.as_array().unwrap().len(), 2);
+        assert_eq!(as_json(&gzip), records);
+    }
+
+    #[test]
+    fn reads_null_tokens_as_missing() {
+        let defaults = NumberFormat::default();
+        assert_eq!(parse_float_optional("---", &defaults), None);
+        assert_eq!(parse_float_optional(" NaN ", &defaults), None);
+        assert_eq!(parse_float_optional(" -95 ", &defaults), Some(-95.0));
+        assert_eq!(parse_float_optional("inf", &defaults), None);
+
+        let csv = "Timestamp,Level\n2025-10-03 10:20:09,-999\n";
+        assert_eq!(parse(csv)[0].level, Some(-999.0));
+        let records = LogParser::new(1000, false)
+            .with_null_tokens(&[" -999".to_string()])
+            .parse_reader(csv.as_bytes(), Delimiter::Auto)
+            .unwrap();
+        assert_eq!(records[0].level, None);
+    }
+
+    #[test]
+    fn reads_decimal_commas_when_enabled() {
+        let comma = NumberFormat {
+            decimal_comma: true,
+            ..NumberFormat::default()
+        };
+        assert_eq!(parse_float_optional("-95,3", &comma), Some(-95.3));
+        assert_eq!(parse_float_optional("-95.3", &comma), Some(-95.3));
+        // Grouped thousands are ambiguous and left alone
+        assert_eq!(parse_float_optional("1,234.5", &comma), None);
+        assert_eq!(parse_float_optional("1,234,567", &comma), None);
+        assert_eq!(
+            parse_float_optional("-95,3", &NumberFormat::default()),
+            None
+        );
+
+        let records = LogParser::new(1000, false)
+            .with_decimal_comma(true)
+            .parse_reader(
+                "Timestamp;Level;Speed\n2025-10-03 10:20:09;-95,3;12.5\n".as_bytes(),
+                Delimiter::Auto,
+            )
+            .unwrap();
+        assert_eq!(records[0].level, Some(-95.3));
+        assert_eq!(records[0].speed, Some(12.5));
+    }
+
+    #[test]
+    fn skips_comment_and_blank_lines() {
+        let csv = "# Exported by logger 1.2\n\
+                   \n\
+                   # device: pixel7\n\
+                   Timestamp,Level\n\
+                   2025-10-03 10:20:09,-95\n\
+                   \n\
+                   # session 2\n\
+                   2025-10-03 10:20:10,-96\n\
+                   \n";
+        let records = parse(csv);
+        assert_eq!(
+            records.iter().map(|r| r.level).collect::<Vec<_>>(),
+            [Some(-95.0), Some(-96.0)]
+        );
+
+        // Without a comment character a comment line is read as a row
+        let result = LogParser::new(1000, false)
+            .with_comment_char(None)
+            .parse_reader(
+                "Timestamp,Level\n# note\n2025-10-03 10:20:09,-95\n".as_bytes(),
+                Delimiter::Auto,
+            );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn timestamp_errors_name_the_column_and_value() {
+        let error = parse_timestamp("yesterday", "Time", &utc_offset()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to parse timestamp in column 'Time': 'yesterday'"
+        );
+
+        let error = LogParser::new(1000, false)
+            .parse_reader(
+                "Time,Level\n2025-10-03 10:20:09,-95\n03/10/2025,-96\n".as_bytes(),
+                Delimiter::Auto,
+            )
+            .unwrap_err();
+        let message = format!("{error:#}");
+        assert!(message.contains("column 'Time'"), "{message}");
+        assert!(message.contains("'03/10/2025'"), "{message}");
+        assert!(message.contains("line 3"), "{message}");
+    }
+
+    #[test]
+    fn keeps_unknown_columns_as_string_fields_when_enabled() {
+        let csv = "Timestamp\tLevel\tPCI\tCA Band\t_x\n\
+                   2025-10-03 10:20:09\t-95\t123\tB1+B3\t\n";
+        let records = LogParser::new(1000, false)
+            .with_keep_unknown_columns(true)
+            .parse_reader(csv.as_bytes(), Delimiter::Auto)
+            .unwrap();
+        let mut extra: Vec<_> = records[0].extra.iter().collect();
+        extra.sort_by(|a, b| a.0.cmp(b.0));
+        assert_eq!(
+            extra,
+            [
+                (
+                    &"ca_band".to_string(),
+                    &FieldValue::Text("B1+B3".to_string())
+                ),
+                (&"pci".to_string(), &FieldValue::Text("123".to_string())),
+            ]
+        );
+
+        // Dropped by default
+        assert!(parse(csv)[0].extra.is_empty());
+    }
+
+    #[test]
+    fn header_priority_picks_among_conflicting_columns() {
+        let csv = "Timestamp\tLevel\tRxLevel\tRSRP\n\
+                   2025-10-03 10:20:09\t-95\t-80\t-101\n";
+        let level = |priority: &[&str]| {
+            let priority: Vec<String> = priority.iter().map(|p| p.to_string()).collect();
+            LogParser::new(1000, false)
+                .with_header_priority(&priority)
+                .parse_reader(csv.as_bytes(), Delimiter::Auto)
+                .unwrap()[0]
+                .level
+        };
+
+        // The last column wins by default
+        assert_eq!(level(&[]), Some(-101.0));
+        assert_eq!(level(&["RxLevel"]), Some(-80.0));
+        // The first listed header present in the file wins, matching in any case
+        assert_eq!(level(&["signal", " level ", "rxlevel"]), Some(-95.0));
+    }
+
+    #[test]
+    fn converts_speeds_from_the_configured_unit() {
+        let csv = "Timestamp\tSpeed\n2025-10-03 10:20:09\t10\n";
+        let speed = |unit: SpeedUnit| {
+            LogParser::new(1000, false)
+                .with_speed_unit(unit)
+                .parse_reader(csv.as_bytes(), Delimiter::Auto)
+                .unwrap()[0]
+                .speed
+        };
+        assert_eq!(speed(SpeedUnit::Kmh), Some(10.0));
+        assert_eq!(speed(SpeedUnit::Ms), Some(36.0));
+        assert_eq!(speed(SpeedUnit::Mph), Some(16.09344));
+    }
+
+    #[test]
+    fn rejects_files_without_a_known_column() {
+        let csv = "Name,Price,Quantity\nApple,120,3\n";
+        let error = LogParser::new(1000, false)
+            .parse_reader(csv.as_bytes(), Delimiter::Auto)
+            .unwrap_err();
+        assert!(
+            error.to_string().contains(
+                "None of the columns (Name, Price, Quantity) is a known G-NetTrack column"
+            ),
+            "{error}"
+        );
+
+        // Mapped columns are the user's say-so that the file is meant to be read
+        let column_map = HashMap::from([(
+            "Price".to_string(),
+            ColumnMapping {
+                name: "price".to_string(),
+                kind: ColumnType::FloatField,
+            },
+        )]);
+        let records = LogParser::new(1000, false)
+            .with_column_map(&column_map)
+            .parse_reader(csv.as_bytes(), Delimiter::Auto)
+            .unwrap();
+        assert_eq!(records[0].extra["price"], FieldValue::Float(120.0));
+    }
+
+    #[test]
+    fn a_batch_size_of_zero_falls_back_to_the_default() {
+        assert_eq!(LogParser::new(0, false).batch_size(), DEFAULT_BATCH_SIZE);
+        assert_eq!(LogParser::new(250, false).batch_size(), 250);
     }
 }
@@ -0,0 +1,895 @@
+use crate::config::OutOfRange;
+use crate::influx_client::FieldValue;
+use crate::parser::GNetTrackRecord;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, TimeDelta, Utc};
+use log::{debug, warn};
+use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+
+/// Keeps records with `since <= timestamp < until`. A missing bound leaves that side open.
+pub fn filter_by_time(
+    records: Vec<GNetTrackRecord>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Vec<GNetTrackRecord> {
+    records
+        .into_iter()
+        .filter(|record| since.is_none_or(|since| record.timestamp >= since))
+        .filter(|record| until.is_none_or(|until| record.timestamp < until))
+        .collect()
+}
+
+/// Geographic area given as `min_lon,min_lat,max_lon,max_lat`. Edges are inside the box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+impl BoundingBox {
+    pub fn parse(value: &str) -> Result<Self> {
+        let parts = value
+            .split(',')
+            .map(|part| {
+                part.trim()
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("Invalid bounding box coordinate: {part}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let [min_lon, min_lat, max_lon, max_lat] = parts[..] else {
+            return Err(anyhow!(
+                "Bounding box must have 4 values (min_lon,min_lat,max_lon,max_lat), got {}",
+                parts.len()
+            ));
+        };
+        if min_lon > max_lon || min_lat > max_lat {
+            return Err(anyhow!(
+                "Bounding box minimum must not exceed maximum: {value}"
+            ));
+        }
+
+        Ok(Self {
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+        })
+    }
+
+    pub fn contains(&self, longitude: f64, latitude: f64) -> bool {
+        (self.min_lon..=self.max_lon).contains(&longitude)
+            && (self.min_lat..=self.max_lat).contains(&latitude)
+    }
+}
+
+/// Keeps records positioned inside `bbox`. Records without coordinates are dropped.
+pub fn filter_by_bbox(records: Vec<GNetTrackRecord>, bbox: &BoundingBox) -> Vec<GNetTrackRecord> {
+    records
+        .into_iter()
+        .filter(|record| match (record.longitude, record.latitude) {
+            (Some(longitude), Some(latitude)) => bbox.contains(longitude, latitude),
+            _ => false,
+        })
+        .collect()
+}
+
+/// Groups records into fixed `bucket`-long windows and emits one record per window, stamped
+/// with the window start. Numeric values are averaged over the records that have them;
+/// everything else is taken from the first record in the window.
+pub fn downsample(records: Vec<GNetTrackRecord>, bucket: Duration) -> Vec<GNetTrackRecord> {
+    let bucket_secs = bucket.as_secs().max(1) as i64;
+    let mut buckets: BTreeMap<i64, Vec<GNetTrackRecord>> = BTreeMap::new();
+    for record in records {
+        let start = record.timestamp.timestamp().div_euclid(bucket_secs) * bucket_secs;
+        buckets.entry(start).or_default().push(record);
+    }
+
+    buckets
+        .into_iter()
+        .map(|(start, group)| merge_bucket(start, group))
+        .collect()
+}
+
+fn merge_bucket(start: i64, group: Vec<GNetTrackRecord>) -> GNetTrackRecord {
+    let mean = |value: fn(&GNetTrackRecord) -> Option<f64>| {
+        let values: Vec<f64> = group.iter().filter_map(value).collect();
+        (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+    };
+
+    let mut merged = group[0].clone();
+    merged.timestamp = DateTime::from_timestamp(start, 0).unwrap_or(merged.timestamp);
+    merged.longitude = mean(|r| r.longitude);
+    merged.latitude = mean(|r| r.latitude);
+    merged.altitude = mean(|r| r.altitude);
+    merged.speed = mean(|r| r.speed);
+    merged.level = mean(|r| r.level);
+    merged.qual = mean(|r| r.qual);
+    merged.snr = mean(|r| r.snr);
+    merged.cqi = mean(|r| r.cqi);
+    merged.dl_bitrate = mean(|r| r.dl_bitrate);
+    merged.ul_bitrate = mean(|r| r.ul_bitrate);
+    merged.ping_avg = mean(|r| r.ping_avg);
+    merged.ping_min = mean(|r| r.ping_min);
+    merged.ping_max = mean(|r| r.ping_max);
+    merged.ping_loss = mean(|r| r.ping_loss);
+    merged.gps_interpolated = group.iter().any(|r| r.gps_interpolated);
+    merged
+}
+
+/// Accessor of a checked numeric field.
+type FieldSlot = fn(&mut GNetTrackRecord) -> &mut Option<f64>;
+
+/// Plausible range of each checked field: name, minimum, maximum (inclusive) and accessor.
+/// `level` covers RSRP and RSCP alike.
+const VALUE_RANGES: [(&str, f64, f64, FieldSlot); 4] = [
+    ("speed", 0.0, 400.0, |record| &mut record.speed),
+    ("level", -140.0, -30.0, |record| &mut record.level),
+    ("snr", -20.0, 40.0, |record| &mut record.snr),
+    ("cqi", 0.0, 15.0, |record| &mut record.cqi),
+];
+
+/// Clamps or nulls the values outside [`VALUE_RANGES`] according to `action`, warning with
+/// a count per field. Records are kept either way.
+pub fn enforce_ranges(
+    mut records: Vec<GNetTrackRecord>,
+    action: OutOfRange,
+) -> Vec<GNetTrackRecord> {
+    if action == OutOfRange::Keep {
+        return records;
+    }
+
+    for (name, min, max, slot) in VALUE_RANGES {
+        let mut fixed = 0;
+        for record in &mut records {
+            let timestamp = record.timestamp;
+            let value = slot(record);
+            let Some(v) = *value else { continue };
+            if (min..=max).contains(&v) {
+                continue;
+            }
+            *value = match action {
+                OutOfRange::Clamp if !v.is_nan() => Some(v.clamp(min, max)),
+                _ => None,
+            };
+            debug!("Out-of-range {name} {v} at {timestamp} replaced with {value:?}");
+            fixed += 1;
+        }
+        if fixed > 0 {
+            let verb = if action == OutOfRange::Clamp {
+                "Clamped"
+            } else {
+                "Dropped"
+            };
+            warn!("{verb} {fixed} {name} values outside [{min}, {max}]");
+        }
+    }
+    records
+}
+
+/// Lower bounds in dBm of the excellent, good and fair buckets; anything below is poor.
+const RSRP_THRESHOLDS: [f64; 3] = [-80.0, -90.0, -100.0];
+const RSCP_THRESHOLDS: [f64; 3] = [-75.0, -85.0, -95.0];
+const RXLEVEL_THRESHOLDS: [f64; 3] = [-70.0, -85.0, -100.0];
+
+/// Buckets a signal level into excellent/good/fair/poor using the measure `tech` reports in
+/// `level`: RSRP for LTE and 5G, RSCP for UMTS, RxLevel for GSM. Unknown techs return `None`.
+pub fn classify(level: f64, tech: &str) -> Option<&'static str> {
+    let thresholds = match tech.trim().to_uppercase().as_str() {
+        "LTE" | "4G" | "LTE-A" | "5G" | "NR" | "NR-NSA" | "NR-SA" => RSRP_THRESHOLDS,
+        "UMTS" | "3G" | "WCDMA" | "HSPA" | "HSPA+" | "HSDPA" | "HSUPA" => RSCP_THRESHOLDS,
+        "GSM" | "2G" | "EDGE" | "GPRS" => RXLEVEL_THRESHOLDS,
+        _ => return None,
+    };
+    let [excellent, good, fair] = thresholds;
+    Some(if level >= excellent {
+        "excellent"
+    } else if level >= good {
+        "good"
+    } else if level >= fair {
+        "fair"
+    } else {
+        "poor"
+    })
+}
+
+/// Sets `signal_quality` on records that have both a level and a known network tech.
+pub fn classify_signal(mut records: Vec<GNetTrackRecord>) -> Vec<GNetTrackRecord> {
+    for record in &mut records {
+        record.signal_quality = record
+            .level
+            .zip(record.network_tech.as_deref())
+            .and_then(|(level, tech)| classify(level, tech))
+            .map(str::to_string);
+    }
+    records
+}
+
+/// Combines the records of several inputs into one stream ordered by timestamp. The sort is
+/// stable, so records with equal timestamps keep their input order.
+pub fn merge_sorted(parsed: Vec<Vec<GNetTrackRecord>>) -> Vec<GNetTrackRecord> {
+    let mut merged: Vec<GNetTrackRecord> = parsed.into_iter().flatten().collect();
+    merged.sort_by_key(|record| record.timestamp);
+    merged
+}
+
+/// Sets `mcc` and `mnc` from operator codes of the form `<digits>-<digits>`, keeping
+/// `operator_code` itself. Other codes are left untouched.
+pub fn split_operator_code(mut records: Vec<GNetTrackRecord>) -> Vec<GNetTrackRecord> {
+    for record in &mut records {
+        let Some((mcc, mnc)) = record.operator_code.as_deref().and_then(parse_mcc_mnc) else {
+            continue;
+        };
+        record.mcc = Some(mcc.to_string());
+        record.mnc = Some(mnc.to_string());
+    }
+    records
+}
+
+fn parse_mcc_mnc(code: &str) -> Option<(&str, &str)> {
+    let (mcc, mnc) = code.trim().split_once('-')?;
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    (is_digits(mcc) && is_digits(mnc)).then_some((mcc, mnc))
+}
+
+/// Mean Earth radius in meters.
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// Great-circle distance in meters between two points given in degrees.
+pub fn haversine(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Sorts `records` by time and sets `distance_m` to the distance from the previous fix.
+/// The first record, records without coordinates and the fix right after one of those get
+/// `None`.
+pub fn derive_distance(mut records: Vec<GNetTrackRecord>) -> Vec<GNetTrackRecord> {
+    records.sort_by_key(|record| record.timestamp);
+
+    let mut previous: Option<(f64, f64)> = None;
+    for record in &mut records {
+        let current = record.latitude.zip(record.longitude);
+        record.distance_m = match (previous, current) {
+            (Some((lat1, lon1)), Some((lat2, lon2))) => Some(haversine(lat1, lon1, lat2, lon2)),
+            _ => None,
+        };
+        previous = current;
+    }
+    records
+}
+
+/// Sorts `records` by time and fills missing coordinates of records lying between two fixes,
+/// interpolating latitude and longitude linearly by timestamp. Records before the first or
+/// after the last fix keep `None`. Filled records get `gps_interpolated`.
+pub fn interpolate_gps(mut records: Vec<GNetTrackRecord>) -> Vec<GNetTrackRecord> {
+    records.sort_by_key(|record| record.timestamp);
+
+    let fixes: Vec<(usize, f64, f64)> = records
+        .iter()
+        .enumerate()
+        .filter_map(|(i, record)| Some((i, record.latitude?, record.longitude?)))
+        .collect();
+    for pair in fixes.windows(2) {
+        let [(start, lat1, lon1), (end, lat2, lon2)] = [pair[0], pair[1]];
+        let t1 = records[start].timestamp;
+        let span = (records[end].timestamp - t1).as_seconds_f64();
+        for record in &mut records[start + 1..end] {
+            let ratio = if span > 0.0 {
+                (record.timestamp - t1).as_seconds_f64() / span
+            } else {
+                0.0
+            };
+            record.latitude = Some(lat1 + (lat2 - lat1) * ratio);
+            record.longitude = Some(lon1 + (lon2 - lon1) * ratio);
+            record.gps_interpolated = true;
+        }
+    }
+    records
+}
+
+/// Tags taken from input file names by `processing.filename_tag_regex`.
+#[derive(Debug, Clone)]
+pub struct FilenameTags {
+    regex: Regex,
+}
+
+impl FilenameTags {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| anyhow!("Invalid filename_tag_regex '{pattern}': {e}"))?;
+        Ok(Self { regex })
+    }
+
+    /// Names of the tags the pattern can produce.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.regex.capture_names().flatten()
+    }
+
+    /// Captured groups of `path`'s file stem (without `.gz` and the format extension) as
+    /// tag names and values. Returns nothing if the stem doesn't match.
+    pub fn tags_for(&self, path: &Path) -> Vec<(String, String)> {
+        let path = match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("gz") => path.with_extension(""),
+            _ => path.to_path_buf(),
+        };
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            return Vec::new();
+        };
+        let Some(captures) = self.regex.captures(stem) else {
+            debug!("File name {stem} doesn't match filename_tag_regex");
+            return Vec::new();
+        };
+        self.names()
+            .filter_map(|name| {
+                let value = captures.name(name)?.as_str();
+                (!value.is_empty()).then(|| (name.to_string(), value.to_string()))
+            })
+            .collect()
+    }
+
+    /// Adds the tags of `path` to every record as `extra` values.
+    pub fn apply(&self, mut records: Vec<GNetTrackRecord>, path: &Path) -> Vec<GNetTrackRecord> {
+        let tags = self.tags_for(path);
+        for record in &mut records {
+            for (name, value) in &tags {
+                record
+                    .extra
+                    .insert(name.clone(), FieldValue::Text(value.clone()));
+            }
+        }
+        records
+    }
+}
+
+/// Initial bearing (forward azimuth) in degrees clockwise from north, in `[0, 360)`, of the
+/// great-circle path from the first point to the second, both given in degrees.
+pub fn initial_bearing(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lon = (lon2 - lon1).to_radians();
+    let y = d_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Sorts `records` by time and sets `bearing_deg` to the direction of travel from the
+/// previous fix. `None` goes to the same records as with [`derive_distance`].
+pub fn derive_bearing(mut records: Vec<GNetTrackRecord>) -> Vec<GNetTrackRecord> {
+    records.sort_by_key(|record| record.timestamp);
+
+    let mut previous: Option<(f64, f64)> = None;
+    for record in &mut records {
+        let current = record.latitude.zip(record.longitude);
+        record.bearing_deg = match (previous, current) {
+            (Some((lat1, lon1)), Some((lat2, lon2))) => {
+                Some(initial_bearing(lat1, lon1, lat2, lon2))
+            }
+            _ => None,
+        };
+        previous = current;
+    }
+    records
+}
+
+/// Parses a signed offset such as `+72h`, `-30m` or `90s` (units: s, m, h, d, w).
+pub fn parse_time_shift(value: &str) -> Result<TimeDelta> {
+    let value = value.trim();
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (number, unit_secs) = match rest.char_indices().last() {
+        Some((i, 's')) => (&rest[..i], 1),
+        Some((i, 'm')) => (&rest[..i], 60),
+        Some((i, 'h')) => (&rest[..i], 3600),
+        Some((i, 'd')) => (&rest[..i], 86400),
+        Some((i, 'w')) => (&rest[..i], 604800),
+        _ => ("", 0),
+    };
+    number
+        .parse::<i64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit_secs * sign))
+        .and_then(TimeDelta::try_seconds)
+        .ok_or_else(|| anyhow!("Invalid time shift '{value}', expected e.g. +72h, -30m or 90s"))
+}
+
+/// Offset that moves the newest of `records` to `now`, keeping the spacing of the others.
+/// Zero without records.
+pub fn shift_to_now(records: &[GNetTrackRecord], now: DateTime<Utc>) -> TimeDelta {
+    records
+        .iter()
+        .map(|record| record.timestamp)
+        .max()
+        .map_or(TimeDelta::zero(), |newest| now - newest)
+}
+
+/// Adds `shift` to every timestamp.
+pub fn shift_timestamps(
+    mut records: Vec<GNetTrackRecord>,
+    shift: TimeDelta,
+) -> Vec<GNetTrackRecord> {
+    for record in &mut records {
+        record.timestamp += shift;
+    }
+    records
+}
+
+/// Fields that identify a measurement for deduplication. Coordinates are compared by their
+/// bit pattern so that identical parsed values always match.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct RecordKey {
+    timestamp: DateTime<Utc>,
+    cell_id: Option<String>,
+    latitude: Option<u64>,
+    longitude: Option<u64>,
+}
+
+impl RecordKey {
+    fn of(record: &GNetTrackRecord) -> Self {
+        Self {
+            timestamp: record.timestamp,
+            cell_id: record.cell_id.clone(),
+            latitude: record.latitude.map(f64::to_bits),
+            longitude: record.longitude.map(f64::to_bits),
+        }
+    }
+}
+
+/// Drops records already seen on `(timestamp, cell_id, latitude, longitude)`. The seen set is
+/// kept between calls so duplicates are also found across input files.
+#[derive(Default)]
+pub struct Deduper {
+    seen: HashSet<RecordKey>,
+}
+
+impl Deduper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `records` without duplicates, keeping the first occurrence and the original order.
+    pub fn dedupe(&mut self, records: Vec<GNetTrackRecord>) -> Vec<GNetTrackRecord> {
+        records
+            .into_iter()
+            .filter(|record| self.seen.insert(RecordKey::of(record)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::record;
+
+    #[test]
+    fn dedupe_drops_repeated_records_across_calls() {
+        let first = r#"{"timestamp": "2025-10-03T10:20:09Z", "cell_id": "1",
+                        "latitude": 35.7, "longitude": 139.5, "level": -95.0}"#;
+        let mut deduper = Deduper::new();
+        let kept = deduper.dedupe(vec![
+            record(first),
+            // Same key with a different level is still a duplicate
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:09Z", "cell_id": "1",
+                    "latitude": 35.7, "longitude": 139.5, "level": -90.0}"#,
+            ),
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:09Z", "cell_id": "2",
+                    "latitude": 35.7, "longitude": 139.5}"#,
+            ),
+            record(r#"{"timestamp": "2025-10-03T10:20:09Z", "cell_id": "1"}"#),
+        ]);
+        assert_eq!(kept.len(), 3);
+        assert_eq!(kept[0].level, Some(-95.0));
+        assert_eq!(kept[1].cell_id.as_deref(), Some("2"));
+        assert_eq!(kept[2].latitude, None);
+
+        assert!(deduper.dedupe(vec![record(first)]).is_empty());
+    }
+
+    fn at_seconds(seconds: &[u32]) -> Vec<GNetTrackRecord> {
+        seconds
+            .iter()
+            .map(|s| record(&format!(r#"{{"timestamp": "2025-10-03T10:20:{s:02}Z"}}"#)))
+            .collect()
+    }
+
+    fn seconds_of(records: &[GNetTrackRecord]) -> Vec<u32> {
+        use chrono::Timelike;
+        records.iter().map(|r| r.timestamp.second()).collect()
+    }
+
+    #[test]
+    fn filter_by_time_keeps_the_half_open_range() {
+        let time = |s: u32| Some(format!("2025-10-03T10:20:{s:02}Z").parse().unwrap());
+        let records = at_seconds(&[1, 2, 3, 4, 5]);
+
+        let both = filter_by_time(records.clone(), time(2), time(4));
+        assert_eq!(seconds_of(&both), [2, 3]);
+        let since = filter_by_time(records.clone(), time(4), None);
+        assert_eq!(seconds_of(&since), [4, 5]);
+        let until = filter_by_time(records.clone(), None, time(3));
+        assert_eq!(seconds_of(&until), [1, 2]);
+        assert_eq!(filter_by_time(records, None, None).len(), 5);
+    }
+
+    #[test]
+    fn filter_by_bbox_includes_the_edges() {
+        let bbox = BoundingBox::parse("139.0, 35.0, 140.0, 36.0").unwrap();
+        let records = vec![
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:01Z", "longitude": 139.5, "latitude": 35.5}"#,
+            ),
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:02Z", "longitude": 140.0, "latitude": 35.0}"#,
+            ),
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:03Z", "longitude": 140.1, "latitude": 35.5}"#,
+            ),
+            record(r#"{"timestamp": "2025-10-03T10:20:04Z", "latitude": 35.5}"#),
+        ];
+
+        assert_eq!(seconds_of(&filter_by_bbox(records, &bbox)), [1, 2]);
+    }
+
+    #[test]
+    fn rejects_malformed_bounding_boxes() {
+        assert!(BoundingBox::parse("139,35,140").is_err());
+        assert!(BoundingBox::parse("139,35,east,36").is_err());
+        assert!(BoundingBox::parse("140,35,139,36").is_err());
+    }
+
+    #[test]
+    fn downsample_averages_each_bucket() {
+        let records = [
+            (0, Some(-90.0)),
+            (3, Some(-100.0)),
+            (4, None),
+            (7, None),
+            (12, Some(-80.0)),
+        ]
+        .iter()
+        .map(|(s, level)| {
+            let mut record = record(&format!(
+                r#"{{"timestamp": "2025-10-03T10:20:{s:02}Z", "cell_id": "c{s}"}}"#
+            ));
+            record.level = *level;
+            record
+        })
+        .collect();
+
+        let merged = downsample(records, Duration::from_secs(5));
+        assert_eq!(seconds_of(&merged), [0, 5, 10]);
+        assert_eq!(merged[0].level, Some(-95.0));
+        assert_eq!(merged[0].cell_id.as_deref(), Some("c0"));
+        // Every level in the bucket is missing
+        assert_eq!(merged[1].level, None);
+        assert_eq!(merged[1].cell_id.as_deref(), Some("c7"));
+        // The final bucket holds a single record
+        assert_eq!(merged[2].level, Some(-80.0));
+    }
+
+    #[test]
+    fn haversine_matches_a_known_distance() {
+        // Tokyo Station to Shin-Osaka Station is about 403 km
+        let distance = haversine(35.6812, 139.7671, 34.7025, 135.4959);
+        assert!((distance - 403_058.0).abs() < 100.0, "{distance}");
+        assert_eq!(haversine(35.0, 139.0, 35.0, 139.0), 0.0);
+    }
+
+    #[test]
+    fn derive_distance_restarts_after_missing_coordinates() {
+        let mut records = at_seconds(&[3, 0, 1, 2, 4]);
+        let positions = [
+            Some((35.003, 139.0)),
+            Some((35.0, 139.0)),
+            Some((35.001, 139.0)),
+            None,
+            Some((35.004, 139.0)),
+        ];
+        for (record, position) in records.iter_mut().zip(positions) {
+            record.latitude = position.map(|p| p.0);
+            record.longitude = position.map(|p| p.1);
+        }
+
+        let records = derive_distance(records);
+        assert_eq!(seconds_of(&records), [0, 1, 2, 3, 4]);
+        let distances: Vec<Option<i64>> = records
+            .iter()
+            .map(|r| r.distance_m.map(|d| d.round() as i64))
+            .collect();
+        // The fix after the gap has nothing to measure from
+        assert_eq!(distances, [None, Some(111), None, None, Some(111)]);
+    }
+
+    #[test]
+    fn splits_well_formed_operator_codes() {
+        let codes = [
+            "440-51",
+            " 310-260 ",
+            "44051",
+            "440-",
+            "-51",
+            "44a-51",
+            "440-51-1",
+        ];
+        let records = codes
+            .iter()
+            .map(|code| {
+                let mut record = record(r#"{"timestamp": "2025-10-03T10:20:09Z"}"#);
+                record.operator_code = Some(code.to_string());
+                record
+            })
+            .collect();
+
+        let records = split_operator_code(records);
+        let split: Vec<_> = records
+            .iter()
+            .map(|r| (r.mcc.as_deref(), r.mnc.as_deref()))
+            .collect();
+        assert_eq!(
+            split,
+            [
+                (Some("440"), Some("51")),
+                (Some("310"), Some("260")),
+                (None, None),
+                (None, None),
+                (None, None),
+                (None, None),
+                (None, None),
+            ]
+        );
+        assert_eq!(records[0].operator_code.as_deref(), Some("440-51"));
+    }
+
+    #[test]
+    fn merges_overlapping_inputs_by_time() {
+        let tagged = |seconds: &[u32], file: &str| {
+            let mut records = at_seconds(seconds);
+            for record in &mut records {
+                record.cell_id = Some(file.to_string());
+            }
+            records
+        };
+        let merged = merge_sorted(vec![tagged(&[1, 3, 5, 7], "a"), tagged(&[2, 3, 6], "b")]);
+
+        assert_eq!(seconds_of(&merged), [1, 2, 3, 3, 5, 6, 7]);
+        // Equal timestamps keep the input order
+        let files: Vec<_> = merged
+            .iter()
+            .map(|r| r.cell_id.as_deref().unwrap())
+            .collect();
+        assert_eq!(files, ["a", "b", "a", "b", "a", "b", "a"]);
+    }
+
+    #[test]
+    fn classifies_levels_at_each_boundary() {
+        let cases = [
+            ("LTE", RSRP_THRESHOLDS),
+            ("nr", RSRP_THRESHOLDS),
+            ("UMTS", RSCP_THRESHOLDS),
+            ("GSM", RXLEVEL_THRESHOLDS),
+        ];
+        for (tech, [excellent, good, fair]) in cases {
+            let buckets = [
+                excellent,
+                excellent - 0.1,
+                good,
+                good - 0.1,
+                fair,
+                fair - 0.1,
+            ]
+            .map(|level| classify(level, tech).unwrap());
+            assert_eq!(
+                buckets,
+                ["excellent", "good", "good", "fair", "fair", "poor"],
+                "{tech}"
+            );
+        }
+        assert_eq!(classify(-80.0, "LTE"), Some("excellent"));
+        assert_eq!(classify(-75.0, "UMTS"), Some("excellent"));
+        assert_eq!(classify(-100.1, "GSM"), Some("poor"));
+        assert_eq!(classify(-80.0, "WiFi"), None);
+    }
+
+    #[test]
+    fn classify_signal_needs_a_level_and_a_known_tech() {
+        let records = classify_signal(vec![
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:09Z", "level": -85.0, "network_tech": "LTE"}"#,
+            ),
+            record(r#"{"timestamp": "2025-10-03T10:20:10Z", "network_tech": "LTE"}"#),
+            record(r#"{"timestamp": "2025-10-03T10:20:11Z", "level": -85.0}"#),
+        ]);
+        let quality: Vec<_> = records
+            .iter()
+            .map(|r| r.signal_quality.as_deref())
+            .collect();
+        assert_eq!(quality, [Some("good"), None, None]);
+    }
+
+    #[test]
+    fn interpolates_gaps_between_fixes_only() {
+        let mut records = at_seconds(&[0, 1, 2, 4, 5, 6]);
+        let positions = [
+            None,
+            Some((35.0, 139.0)),
+            None,
+            None,
+            Some((35.4, 139.8)),
+            None,
+        ];
+        for (record, position) in records.iter_mut().zip(positions) {
+            record.latitude = position.map(|p| p.0);
+            record.longitude = position.map(|p| p.1);
+        }
+
+        let records = interpolate_gps(records);
+        let position = |r: &GNetTrackRecord| {
+            r.latitude.zip(r.longitude).map(|(lat, lon)| {
+                (
+                    (lat * 1000.0).round() / 1000.0,
+                    (lon * 1000.0).round() / 1000.0,
+                )
+            })
+        };
+        let positions: Vec<_> = records.iter().map(position).collect();
+        assert_eq!(
+            positions,
+            [
+                None,
+                Some((35.0, 139.0)),
+                Some((35.1, 139.2)),
+                Some((35.3, 139.6)),
+                Some((35.4, 139.8)),
+                None,
+            ]
+        );
+        let interpolated: Vec<_> = records.iter().map(|r| r.gps_interpolated).collect();
+        assert_eq!(interpolated, [false, false, true, true, false, false]);
+    }
+
+    #[test]
+    fn enforce_ranges_fixes_values_past_each_bound() {
+        // Per field: the bounds, then a value just below and just above them
+        let cases = [
+            ("speed", 0.0, 400.0),
+            ("level", -140.0, -30.0),
+            ("snr", -20.0, 40.0),
+            ("cqi", 0.0, 15.0),
+        ];
+        for (name, min, max) in cases {
+            let records: Vec<_> = [min, max, min - 1.0, max + 1.0]
+                .iter()
+                .map(|v| {
+                    record(&format!(
+                        r#"{{"timestamp": "2025-10-03T10:20:09Z", "{name}": {v}}}"#
+                    ))
+                })
+                .collect();
+            let values = |records: Vec<GNetTrackRecord>| -> Vec<Option<f64>> {
+                let slot = VALUE_RANGES.iter().find(|r| r.0 == name).unwrap().3;
+                records.into_iter().map(|mut r| *slot(&mut r)).collect()
+            };
+
+            let kept = values(enforce_ranges(records.clone(), OutOfRange::Keep));
+            assert_eq!(
+                kept,
+                [Some(min), Some(max), Some(min - 1.0), Some(max + 1.0)]
+            );
+            let clamped = values(enforce_ranges(records.clone(), OutOfRange::Clamp));
+            assert_eq!(
+                clamped,
+                [Some(min), Some(max), Some(min), Some(max)],
+                "{name}"
+            );
+            let nulled = values(enforce_ranges(records, OutOfRange::Null));
+            assert_eq!(nulled, [Some(min), Some(max), None, None], "{name}");
+        }
+    }
+
+    #[test]
+    fn filename_tags_come_from_matching_file_stems() {
+        let tags =
+            FilenameTags::parse(r"^G-NetTrack_(?P<device>[^_]+)_(?P<date>\d{4}\.\d{2}\.\d{2})$")
+                .unwrap();
+        assert_eq!(tags.names().collect::<Vec<_>>(), ["device", "date"]);
+
+        let expected = [
+            ("device".to_string(), "Pixel7".to_string()),
+            ("date".to_string(), "2025.10.03".to_string()),
+        ];
+        let path = Path::new("logs/G-NetTrack_Pixel7_2025.10.03.csv");
+        assert_eq!(tags.tags_for(path), expected);
+        let gzipped = Path::new("G-NetTrack_Pixel7_2025.10.03.csv.gz");
+        assert_eq!(tags.tags_for(gzipped), expected);
+
+        let records = tags.apply(at_seconds(&[1, 2]), path);
+        assert!(records.iter().all(|record| {
+            record.extra.get("device") == Some(&FieldValue::Text("Pixel7".to_string()))
+        }));
+
+        let other = Path::new("drive_2025.10.03.csv");
+        assert!(tags.tags_for(other).is_empty());
+        assert!(tags.apply(at_seconds(&[1]), other)[0].extra.is_empty());
+
+        assert!(FilenameTags::parse("(?P<device>").is_err());
+    }
+
+    #[test]
+    fn initial_bearing_points_north_and_east() {
+        assert_eq!(initial_bearing(35.0, 139.0, 36.0, 139.0), 0.0);
+        assert!((initial_bearing(0.0, 139.0, 0.0, 140.0) - 90.0).abs() < 1e-9);
+        assert!((initial_bearing(36.0, 139.0, 35.0, 139.0) - 180.0).abs() < 1e-9);
+        // West stays within [0, 360)
+        assert!((initial_bearing(0.0, 140.0, 0.0, 139.0) - 270.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn derive_bearing_chains_from_the_previous_fix() {
+        let mut records = at_seconds(&[3, 0, 1, 2, 4]);
+        let positions = [
+            None,
+            Some((0.0, 139.0)),
+            Some((0.001, 139.0)),
+            Some((0.001, 139.001)),
+            Some((0.002, 139.001)),
+        ];
+        for (record, position) in records.iter_mut().zip(positions) {
+            record.latitude = position.map(|p| p.0);
+            record.longitude = position.map(|p| p.1);
+        }
+
+        let records = derive_bearing(records);
+        assert_eq!(seconds_of(&records), [0, 1, 2, 3, 4]);
+        let bearings: Vec<Option<i64>> = records
+            .iter()
+            .map(|r| r.bearing_deg.map(|b| b.round() as i64))
+            .collect();
+        // Nothing before the first fix or across the gap
+        assert_eq!(bearings, [None, Some(0), Some(90), None, None]);
+    }
+
+    #[test]
+    fn parses_signed_time_shifts() {
+        assert_eq!(parse_time_shift("+72h").unwrap(), TimeDelta::hours(72));
+        assert_eq!(parse_time_shift("-30m").unwrap(), TimeDelta::minutes(-30));
+        assert_eq!(parse_time_shift("90s").unwrap(), TimeDelta::seconds(90));
+        assert_eq!(parse_time_shift("2w").unwrap(), TimeDelta::weeks(2));
+        for invalid in ["", "72", "h", "+1.5h", "3y"] {
+            assert!(parse_time_shift(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn shifts_timestamps_forward_backward_and_to_now() {
+        let records = at_seconds(&[10, 20, 15]);
+
+        let forward = shift_timestamps(records.clone(), TimeDelta::seconds(5));
+        assert_eq!(seconds_of(&forward), [15, 25, 20]);
+        let backward = shift_timestamps(records.clone(), TimeDelta::seconds(-10));
+        assert_eq!(seconds_of(&backward), [0, 10, 5]);
+
+        let now = "2025-10-04T00:00:00Z".parse().unwrap();
+        let aligned = shift_timestamps(records.clone(), shift_to_now(&records, now));
+        let times: Vec<_> = aligned.iter().map(|r| r.timestamp.to_rfc3339()).collect();
+        assert_eq!(
+            times,
+            [
+                "2025-10-03T23:59:50+00:00",
+                "2025-10-04T00:00:00+00:00",
+                "2025-10-03T23:59:55+00:00",
+            ]
+        );
+        assert_eq!(shift_to_now(&[], now), TimeDelta::zero());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::parser::GNetTrackRecord;
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+/// Minimum, mean and maximum of the values present for one measurement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    /// Summarises the `Some` values, or returns `None` if there are none.
+    fn from_values(values: impl Iterator<Item = Option<f64>>) -> Option<Self> {
+        let mut count = 0usize;
+        let mut sum = 0.0;
+        let mut min = f64::INFINITY;
+        let mut max = f64::NEG_INFINITY;
+        for value in values.flatten() {
+            count += 1;
+            sum += value;
+            min = min.min(value);
+            max = max.max(value);
+        }
+
+        (count > 0).then(|| Self {
+            min,
+            mean: sum / count as f64,
+            max,
+        })
+    }
+}
+
+/// Overview of a set of parsed records, used as a sanity check before uploading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordStats {
+    pub count: usize,
+    pub first_timestamp: Option<DateTime<Utc>>,
+    pub last_timestamp: Option<DateTime<Utc>>,
+    /// Records with both latitude and longitude
+    pub with_gps: usize,
+    pub level: Option<Summary>,
+    pub qual: Option<Summary>,
+    pub snr: Option<Summary>,
+    pub speed: Option<Summary>,
+}
+
+impl RecordStats {
+    pub fn from_records(records: &[GNetTrackRecord]) -> Self {
+        Self {
+            count: records.len(),
+            first_timestamp: records.iter().map(|r| r.timestamp).min(),
+            last_timestamp: records.iter().map(|r| r.timestamp).max(),
+            with_gps: records
+                .iter()
+                .filter(|r| r.latitude.is_some() && r.longitude.is_some())
+                .count(),
+            level: Summary::from_values(records.iter().map(|r| r.level)),
+            qual: Summary::from_values(records.iter().map(|r| r.qual)),
+            snr: Summary::from_values(records.iter().map(|r| r.snr)),
+            speed: Summary::from_values(records.iter().map(|r| r.speed)),
+        }
+    }
+
+    /// Percentage of records with a GPS fix.
+    pub fn gps_percent(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.with_gps as f64 * 100.0 / self.count as f64
+        }
+    }
+}
+
+impl fmt::Display for RecordStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Records    {}", self.count)?;
+        match (self.first_timestamp, self.last_timestamp) {
+            (Some(first), Some(last)) => writeln!(
+                f,
+                "Time span  {} - {} ({}s)",
+                first.to_rfc3339(),
+                last.to_rfc3339(),
+                (last - first).num_seconds()
+            )?,
+            _ => writeln!(f, "Time span  -")?,
+        }
+        writeln!(
+            f,
+            "GPS fix    {:.1}% ({}/{})",
+            self.gps_percent(),
+            self.with_gps,
+            self.count
+        )?;
+
+        let summaries = [
+            ("level", self.level),
+            ("qual", self.qual),
+            ("snr", self.snr),
+            ("speed", self.speed),
+        ];
+        for (i, (name, summary)) in summaries.iter().enumerate() {
+            match summary {
+                Some(s) => write!(
+                    f,
+                    "{name:<10} min {:.2} / mean {:.2} / max {:.2}",
+                    s.min, s.mean, s.max
+                )?,
+                None => write!(f, "{name:<10} no values")?,
+            }
+            if i + 1 < summaries.len() {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::record;
+
+    #[test]
+    fn summarises_records() {
+        let records = [
+            record(
+                r#"{"timestamp": "2025-10-03T10:20:09Z", "latitude": 35.7, "longitude": 139.5,
+                    "level": -90.0, "speed": 10.0}"#,
+            ),
+            record(r#"{"timestamp": "2025-10-03T10:20:19Z", "level": -100.0, "snr": 5.0}"#),
+            record(r#"{"timestamp": "2025-10-03T10:20:14Z", "level": -95.0}"#),
+        ];
+        let stats = RecordStats::from_records(&records);
+
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.first_timestamp, Some(records[0].timestamp));
+        assert_eq!(stats.last_timestamp, Some(records[1].timestamp));
+        assert_eq!(stats.with_gps, 1);
+        assert_eq!(
+            stats.level,
+            Some(Summary {
+                min: -100.0,
+                mean: -95.0,
+                max: -90.0
+            })
+        );
+        assert_eq!(stats.snr.map(|s| s.mean), Some(5.0));
+        assert_eq!(stats.speed.map(|s| s.mean), Some(10.0));
+        assert_eq!(stats.qual, None);
+
+        let report = stats.to_string();
+        assert!(report.contains("(10s)"), "{report}");
+        assert!(report.contains("GPS fix    33.3% (1/3)"), "{report}");
+        assert!(report.contains("qual       no values"), "{report}");
+    }
+
+    #[test]
+    fn handles_no_records() {
+        let stats = RecordStats::from_records(&[]);
+        assert_eq!(stats.gps_percent(), 0.0);
+        assert!(stats.to_string().contains("Time span  -"));
+    }
+}
@@ -0,0 +1,374 @@
+//! Runs the `gnt2influx` binary for each subcommand.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const LOG: &str = "Timestamp\tLongitude\tLatitude\tLevel\n\
+                   2025-10-03 10:20:09\t139.5\t35.7\t-95\n\
+                   2025-10-03 10:20:10\t139.6\t35.8\t-96\n";
+
+/// A directory holding `drive.txt` and a default `config.toml`, written by `--init-config`,
+/// with an InfluxDB 3.x target per URL.
+fn workspace(urls: &[&str]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("drive.txt"), LOG).unwrap();
+    assert!(run(dir.path(), &["--init-config"]).status.success());
+
+    let config_path = dir.path().join("config.toml");
+    let default = fs::read_to_string(&config_path).unwrap();
+    let (target, rest) = default.split_at(default.find("[logging]").unwrap());
+    let target = target
+        .replace("[influxdb]", "[[influxdb]]\nversion = \"3\"")
+        .replace("connect_timeout_secs = 10", "connect_timeout_secs = 1");
+    let targets: String = urls
+        .iter()
+        .map(|url| target.replace("http://localhost:8086", url))
+        .collect();
+    fs::write(&config_path, targets + rest).unwrap();
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gnt2influx"))
+        .current_dir(dir)
+        .args(args)
+        .env_remove("GNT2INFLUX_INFLUXDB_URL")
+        .output()
+        .unwrap()
+}
+
+/// A server answering every request with 200, recording the request lines and bodies.
+struct Server {
+    url: String,
+    requests: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl Server {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let recorded = recorded.clone();
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(&stream);
+                    while let Some(request) = read_request(&mut reader) {
+                        recorded.lock().unwrap().push(request);
+                        let response = b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n";
+                        if (&stream).write_all(response).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// Bodies of the requests to `path`.
+    fn bodies(&self, path: &str) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(line, _)| line.split(' ').nth(1).is_some_and(|t| t.starts_with(path)))
+            .map(|(_, body)| body.clone())
+            .collect()
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> Option<(String, String)> {
+    let mut request_line = String::new();
+    reader
+        .read_line(&mut request_line)
+        .ok()
+        .filter(|n| *n > 0)?;
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse().ok()?;
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    Some((
+        request_line.trim_end().to_string(),
+        String::from_utf8_lossy(&body).into_owned(),
+    ))
+}
+
+/// A URL nothing listens on.
+fn closed_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+#[test]
+fn convert_writes_the_records_to_a_file() {
+    let dir = workspace(&[&closed_url()]);
+    let output = run(
+        dir.path(),
+        &[
+            "convert",
+            "-i",
+            "drive.txt",
+            "-o",
+            "out.ndjson",
+            "--output-format",
+            "ndjson",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    let written = fs::read_to_string(dir.path().join("out.ndjson")).unwrap();
+    assert_eq!(written.lines().count(), 2);
+}
+
+#[test]
+fn stats_prints_a_summary() {
+    let dir = workspace(&[&closed_url()]);
+    let output = run(dir.path(), &["stats", "-i", "drive.txt"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Records    2"), "{stdout}");
+}
+
+#[test]
+fn test_checks_the_connection() {
+    let server = Server::start();
+    let dir = workspace(&[&server.url]);
+    assert!(run(dir.path(), &["test"]).status.success());
+    // The deprecated flag still works
+    assert!(run(dir.path(), &["--test-connection"]).status.success());
+
+    let dir = workspace(&[&closed_url()]);
+    assert!(!run(dir.path(), &["test"]).status.success());
+}
+
+#[test]
+fn upload_dry_run_writes_the_line_protocol() {
+    let dir = workspace(&[&closed_url()]);
+    let output = run(
+        dir.path(),
+        &["upload", "-i", "drive.txt", "--dry-run", "-o", "lines.txt"],
+    );
+    assert!(output.status.success(), "{output:?}");
+    let lines = fs::read_to_string(dir.path().join("lines.txt")).unwrap();
+    assert_eq!(lines.lines().count(), 2);
+    assert!(lines.starts_with("network_measurements,"), "{lines}");
+
+    // So does the flat form, without a subcommand
+    assert!(
+        run(dir.path(), &["-i", "drive.txt", "--dry-run"])
+            .status
+            .success()
+    );
+}
+
+#[test]
+fn flat_flags_before_a_subcommand_are_rejected() {
+    let dir = workspace(&[&closed_url()]);
+    let output = run(dir.path(), &["--dry-run", "stats", "-i", "drive.txt"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--dry-run must be given after the `stats` subcommand"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn upload_continues_past_a_failing_target() {
+    let server = Server::start();
+    let closed = closed_url();
+    let dir = workspace(&[&closed, &server.url]);
+
+    let output = run(dir.path(), &["upload", "-i", "drive.txt"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Upload failed for 1 of 2 targets"),
+        "{stderr}"
+    );
+
+    // The second target still received every record
+    let written: Vec<String> = server
+        .bodies("/api/v3/write_lp")
+        .iter()
+        .flat_map(|body| body.lines().map(str::to_string).collect::<Vec<_>>())
+        .collect();
+    assert_eq!(written.len(), 2, "{written:?}");
+    assert!(
+        written
+            .iter()
+            .all(|line| line.starts_with("network_measurements,"))
+    );
+}
+
+#[test]
+fn measurement_type_flag_overrides_the_config() {
+    let dir = workspace(&[&closed_url()]);
+    let output = run(
+        dir.path(),
+        &[
+            "upload",
+            "-i",
+            "drive.txt",
+            "--dry-run",
+            "-o",
+            "lines.txt",
+            "--measurement-type",
+            "qxdm",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    let lines = fs::read_to_string(dir.path().join("lines.txt")).unwrap();
+    assert!(
+        lines
+            .lines()
+            .all(|line| line.starts_with("network_measurements,measurement_type=qxdm ")),
+        "{lines}"
+    );
+}
+
+#[test]
+fn no_create_db_skips_the_create_database_query() {
+    let creates = |args: &[&str]| {
+        let server = Server::start();
+        let dir = workspace(&[&server.url]);
+        let config_path = dir.path().join("config.toml");
+        let config = fs::read_to_string(&config_path).unwrap();
+        fs::write(
+            &config_path,
+            config.replace("version = \"3\"", "version = \"1\""),
+        )
+        .unwrap();
+
+        let output = run(dir.path(), args);
+        assert!(output.status.success(), "{output:?}");
+        assert!(!server.bodies("/write").is_empty());
+        let requests = server.requests.lock().unwrap();
+        // The connection test runs either way
+        assert!(
+            requests
+                .iter()
+                .any(|(line, _)| line.contains("SHOW+DATABASES"))
+        );
+        requests
+            .iter()
+            .filter(|(line, _)| line.contains("CREATE+DATABASE"))
+            .count()
+    };
+
+    assert_eq!(creates(&["upload", "-i", "drive.txt"]), 1);
+    assert_eq!(creates(&["upload", "-i", "drive.txt", "--no-create-db"]), 0);
+}
+
+#[test]
+fn preview_needs_no_config_or_server() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("drive.txt"), LOG).unwrap();
+
+    let output = run(dir.path(), &["-i", "drive.txt", "--preview", "1"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let rows: Vec<&str> = stdout.lines().collect();
+    assert_eq!(rows.len(), 3, "{stdout}");
+    assert!(rows[0].starts_with("timestamp"), "{stdout}");
+    assert!(rows[2].starts_with("2025-10-03T10:20:09Z"), "{stdout}");
+    assert!(rows[2].contains("-95"), "{stdout}");
+}
+
+#[test]
+fn max_cardinality_aborts_above_the_limit() {
+    let dir = workspace(&[&closed_url()]);
+    fs::write(
+        dir.path().join("cells.txt"),
+        "Timestamp\tCellID\tLevel\n\
+         2025-10-03 10:20:09\t1\t-95\n\
+         2025-10-03 10:20:10\t2\t-96\n\
+         2025-10-03 10:20:11\t3\t-97\n",
+    )
+    .unwrap();
+    let upload = |limit: &str| {
+        run(
+            dir.path(),
+            &[
+                "upload",
+                "-i",
+                "cells.txt",
+                "--dry-run",
+                "--max-cardinality",
+                limit,
+            ],
+        )
+    };
+
+    let output = upload("2");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("would create 3 series, more than the limit of 2"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("tag 'cell_id' has 3 distinct values"),
+        "{stderr}"
+    );
+
+    let output = upload("3");
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn strict_exit_fails_when_records_were_skipped() {
+    // Invalid records are skipped by default
+    let dir = workspace(&[&closed_url()]);
+    fs::write(
+        dir.path().join("bad.txt"),
+        format!("{LOG}yesterday\t139.7\t35.9\t-97\n"),
+    )
+    .unwrap();
+
+    let output = run(dir.path(), &["stats", "-i", "bad.txt", "--strict-exit"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("1 invalid records were skipped while parsing (--strict-exit)"),
+        "{stderr}"
+    );
+
+    assert!(
+        run(dir.path(), &["stats", "-i", "bad.txt"])
+            .status
+            .success()
+    );
+    let output = run(dir.path(), &["stats", "-i", "drive.txt", "--strict-exit"]);
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn operator_flag_is_validated_with_the_config() {
+    let dir = workspace(&[&closed_url()]);
+    let output = run(dir.path(), &["stats", "-i", "drive.txt", "--operator", ""]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("processing.kml_operator_name must not be empty"),
+        "{stderr}"
+    );
+}